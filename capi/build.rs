@@ -0,0 +1,20 @@
+// Generates the C header from the exported functions into OUT_DIR, where a test checks
+// that the committed include/game2048.h still matches it. Set GAME2048_WRITE_HEADER=1 to
+// update include/game2048.h as well.
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+    let header = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("couldn't generate the C header");
+    header.write_to_file(format!("{}/game2048.h", out_dir));
+    if std::env::var_os("GAME2048_WRITE_HEADER").is_some() {
+        header.write_to_file(format!("{}/include/game2048.h", crate_dir));
+    }
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=GAME2048_WRITE_HEADER");
+}
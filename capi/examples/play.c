@@ -0,0 +1,30 @@
+/* Lets the corner bot play a seeded game and prints the result.
+ *
+ *     cargo build --release -p game2048-capi
+ *     cc capi/examples/play.c -Icapi/include target/release/libgame2048_capi.a -lm -lpthread -ldl -o play
+ */
+
+#include <stdio.h>
+
+#include "game2048.h"
+
+int main(void) {
+    Game *game = game2048_new(42, GAME2048_RULES_CLASSIC);
+    while (game2048_status(game) == GAME2048_OK) {
+        int32_t direction = game2048_bot_move(game, "corner", 0);
+        if (direction < 0 || game2048_move(game, (uint32_t)direction) == GAME2048_INVALID_MOVE) {
+            break;
+        }
+    }
+
+    uint64_t cells[16];
+    game2048_cells(game, cells);
+    for (int y = 0; y < 4; y++) {
+        printf("%6llu %6llu %6llu %6llu\n", (unsigned long long)cells[4 * y],
+               (unsigned long long)cells[4 * y + 1], (unsigned long long)cells[4 * y + 2],
+               (unsigned long long)cells[4 * y + 3]);
+    }
+    printf("score %llu\n", (unsigned long long)game2048_score(game));
+    game2048_free(game);
+    return 0;
+}
@@ -0,0 +1,125 @@
+/* Generated by cbindgen from capi/src/lib.rs, don't edit */
+
+#ifndef GAME2048_H
+#define GAME2048_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define GAME2048_RULES_CLASSIC 0
+
+#define GAME2048_RULES_WRAP 1
+
+#define GAME2048_RULES_DIAGONAL 2
+
+#define GAME2048_RULES_WRAP_DIAGONAL 3
+
+#define GAME2048_LEFT 0
+
+#define GAME2048_RIGHT 1
+
+#define GAME2048_UP 2
+
+#define GAME2048_DOWN 3
+
+#define GAME2048_UP_LEFT 4
+
+#define GAME2048_UP_RIGHT 5
+
+#define GAME2048_DOWN_LEFT 6
+
+#define GAME2048_DOWN_RIGHT 7
+
+#define GAME2048_OK 0
+
+#define GAME2048_INVALID_MOVE 1
+
+#define GAME2048_LOST 2
+
+#define GAME2048_ERROR -1
+
+typedef struct Game Game;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Starts a game. Returns null if the rules are unknown.
+ */
+struct Game *game2048_new(uint64_t seed, uint32_t rules);
+
+/*
+ # Safety
+ `game` must be null or a pointer returned by this library that wasn't freed yet.
+ */
+void game2048_free(struct Game *game);
+
+/*
+ Plays a move and spawns a tile. Returns GAME2048_OK, GAME2048_INVALID_MOVE,
+ GAME2048_LOST, or GAME2048_ERROR for an unknown direction.
+
+ # Safety
+ `game` must be null or a live game.
+ */
+int32_t game2048_move(struct Game *game, uint32_t direction);
+
+/*
+ Copies the 16 tile values, row by row, 0 for empty cells. Returns GAME2048_OK or
+ GAME2048_ERROR.
+
+ # Safety
+ `game` must be null or a live game, and `cells` null or room for 16 values.
+ */
+int32_t game2048_cells(const struct Game *game, uint64_t *cells);
+
+/*
+ # Safety
+ `game` must be null or a live game.
+ */
+uint64_t game2048_score(const struct Game *game);
+
+/*
+ GAME2048_OK while moves are left, GAME2048_LOST once there are none
+
+ # Safety
+ `game` must be null or a live game.
+ */
+int32_t game2048_status(const struct Game *game);
+
+/*
+ Writes the game as replay JSON, null terminated, when it fits in `size` bytes. Returns
+ the length of the JSON without the terminator, so a call with a null buffer gives the
+ size to allocate, or -1 on error.
+
+ # Safety
+ `game` must be null or a live game, and `buffer` null or room for `size` bytes.
+ */
+int64_t game2048_serialize(const struct Game *game, char *buffer, size_t size);
+
+/*
+ Restores a game from replay JSON, continuing with a generator seeded by `seed`.
+ Returns null if the JSON isn't a valid replay.
+
+ # Safety
+ `json` must be null or a null terminated string.
+ */
+struct Game *game2048_deserialize(const char *json, uint64_t seed);
+
+/*
+ Asks a built in agent ("expectimax", "corner", ...) for a move. Returns the direction,
+ or GAME2048_ERROR if the agent is unknown or has no move.
+
+ # Safety
+ `game` must be null or a live game, and `agent` null or a null terminated string.
+ */
+int32_t game2048_bot_move(const struct Game *game, const char *agent, uint64_t seed);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* GAME2048_H */
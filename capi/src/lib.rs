@@ -0,0 +1,295 @@
+// C interface to the engine, so other programs play by exactly the same rules as the web
+// game. Games are opaque pointers created by `game2048_new` or `game2048_deserialize`
+// and released with `game2048_free`. Null pointers are accepted everywhere and treated
+// as errors.
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use game2048::agent::agent_from_name;
+use game2048::grid::{GameStatus, Grid, Move, Rules, Topology};
+use game2048::replay::Replay;
+
+pub const GAME2048_RULES_CLASSIC: u32 = 0;
+pub const GAME2048_RULES_WRAP: u32 = 1;
+pub const GAME2048_RULES_DIAGONAL: u32 = 2;
+pub const GAME2048_RULES_WRAP_DIAGONAL: u32 = 3;
+
+pub const GAME2048_LEFT: u32 = 0;
+pub const GAME2048_RIGHT: u32 = 1;
+pub const GAME2048_UP: u32 = 2;
+pub const GAME2048_DOWN: u32 = 3;
+pub const GAME2048_UP_LEFT: u32 = 4;
+pub const GAME2048_UP_RIGHT: u32 = 5;
+pub const GAME2048_DOWN_LEFT: u32 = 6;
+pub const GAME2048_DOWN_RIGHT: u32 = 7;
+
+pub const GAME2048_OK: i32 = 0;
+pub const GAME2048_INVALID_MOVE: i32 = 1;
+pub const GAME2048_LOST: i32 = 2;
+pub const GAME2048_ERROR: i32 = -1;
+
+const MOVES: [Move; 8] = [
+    Move::Left,
+    Move::Right,
+    Move::Up,
+    Move::Down,
+    Move::UpLeft,
+    Move::UpRight,
+    Move::DownLeft,
+    Move::DownRight,
+];
+
+// A game with its own random number generator and the record of its moves
+pub struct Game {
+    grid: Grid,
+    rng: StdRng,
+    replay: Replay,
+}
+
+fn get_rules(rules: u32) -> Option<Rules> {
+    let (topology, diagonal_moves) = match rules {
+        GAME2048_RULES_CLASSIC => (Topology::Flat, false),
+        GAME2048_RULES_WRAP => (Topology::Torus, false),
+        GAME2048_RULES_DIAGONAL => (Topology::Flat, true),
+        GAME2048_RULES_WRAP_DIAGONAL => (Topology::Torus, true),
+        _ => return None,
+    };
+    Some(Rules {
+        topology,
+        diagonal_moves,
+    })
+}
+
+fn get_direction(mov: Move) -> u32 {
+    MOVES.iter().position(|other| *other == mov).unwrap() as u32
+}
+
+/// Starts a game. Returns null if the rules are unknown.
+#[no_mangle]
+pub extern "C" fn game2048_new(seed: u64, rules: u32) -> *mut Game {
+    let rules = match get_rules(rules) {
+        Some(rules) => rules,
+        None => return ptr::null_mut(),
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    let grid = Grid::new_random_with_rng(rules, &mut rng);
+    let replay = Replay::new(&grid);
+    Box::into_raw(Box::new(Game { grid, rng, replay }))
+}
+
+/// # Safety
+/// `game` must be null or a pointer returned by this library that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_free(game: *mut Game) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Plays a move and spawns a tile. Returns GAME2048_OK, GAME2048_INVALID_MOVE,
+/// GAME2048_LOST, or GAME2048_ERROR for an unknown direction.
+///
+/// # Safety
+/// `game` must be null or a live game.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_move(game: *mut Game, direction: u32) -> i32 {
+    let (game, mov) = match (game.as_mut(), MOVES.get(direction as usize)) {
+        (Some(game), Some(mov)) => (game, *mov),
+        _ => return GAME2048_ERROR,
+    };
+    match game.grid.attempt_with_rng(mov, &mut game.rng) {
+        GameStatus::InvalidMove => GAME2048_INVALID_MOVE,
+        status => {
+            game.replay.record(mov, &game.grid);
+            if status == GameStatus::Lost {
+                GAME2048_LOST
+            } else {
+                GAME2048_OK
+            }
+        }
+    }
+}
+
+/// Copies the 16 tile values, row by row, 0 for empty cells. Returns GAME2048_OK or
+/// GAME2048_ERROR.
+///
+/// # Safety
+/// `game` must be null or a live game, and `cells` null or room for 16 values.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_cells(game: *const Game, cells: *mut u64) -> i32 {
+    let game = match game.as_ref() {
+        Some(game) if !cells.is_null() => game,
+        _ => return GAME2048_ERROR,
+    };
+    for (i, cell) in game.grid.cells.iter().flatten().enumerate() {
+        *cells.add(i) = *cell;
+    }
+    GAME2048_OK
+}
+
+/// # Safety
+/// `game` must be null or a live game.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_score(game: *const Game) -> u64 {
+    game.as_ref().map_or(0, |game| game.grid.get_score())
+}
+
+/// GAME2048_OK while moves are left, GAME2048_LOST once there are none
+///
+/// # Safety
+/// `game` must be null or a live game.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_status(game: *const Game) -> i32 {
+    match game.as_ref() {
+        Some(game) if game.grid.has_player_lost() => GAME2048_LOST,
+        Some(_) => GAME2048_OK,
+        None => GAME2048_ERROR,
+    }
+}
+
+/// Writes the game as replay JSON, null terminated, when it fits in `size` bytes. Returns
+/// the length of the JSON without the terminator, so a call with a null buffer gives the
+/// size to allocate, or -1 on error.
+///
+/// # Safety
+/// `game` must be null or a live game, and `buffer` null or room for `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_serialize(
+    game: *const Game,
+    buffer: *mut c_char,
+    size: usize,
+) -> i64 {
+    let game = match game.as_ref() {
+        Some(game) => game,
+        None => return -1,
+    };
+    let json = game.replay.to_json();
+    if !buffer.is_null() && json.len() < size {
+        ptr::copy_nonoverlapping(json.as_ptr(), buffer as *mut u8, json.len());
+        *buffer.add(json.len()) = 0;
+    }
+    json.len() as i64
+}
+
+/// Restores a game from replay JSON, continuing with a generator seeded by `seed`.
+/// Returns null if the JSON isn't a valid replay.
+///
+/// # Safety
+/// `json` must be null or a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_deserialize(json: *const c_char, seed: u64) -> *mut Game {
+    if json.is_null() {
+        return ptr::null_mut();
+    }
+    let replay = match CStr::from_ptr(json).to_str().map(Replay::from_json) {
+        Ok(Ok(replay)) => replay,
+        _ => return ptr::null_mut(),
+    };
+    let grid = match replay.positions() {
+        Ok(mut positions) => positions.pop().unwrap(),
+        Err(_) => return ptr::null_mut(),
+    };
+    let rng = StdRng::seed_from_u64(seed);
+    Box::into_raw(Box::new(Game { grid, rng, replay }))
+}
+
+/// Asks a built in agent ("expectimax", "corner", ...) for a move. Returns the direction,
+/// or GAME2048_ERROR if the agent is unknown or has no move.
+///
+/// # Safety
+/// `game` must be null or a live game, and `agent` null or a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn game2048_bot_move(
+    game: *const Game,
+    agent: *const c_char,
+    seed: u64,
+) -> i32 {
+    if game.is_null() || agent.is_null() {
+        return GAME2048_ERROR;
+    }
+    let agent = CStr::from_ptr(agent)
+        .to_str()
+        .ok()
+        .and_then(|name| agent_from_name(name, seed));
+    match agent.and_then(|mut agent| agent.choose(&(*game).grid)) {
+        Some(mov) => get_direction(mov) as i32,
+        None => GAME2048_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn get_cells(game: *const Game) -> [u64; 16] {
+        let mut cells = [0; 16];
+        assert_eq!(game2048_cells(game, cells.as_mut_ptr()), GAME2048_OK);
+        cells
+    }
+
+    #[test]
+    fn games_play_and_serialize() {
+        unsafe {
+            let game = game2048_new(8, GAME2048_RULES_CLASSIC);
+            let name = c"corner";
+            while game2048_status(game) == GAME2048_OK {
+                let direction = game2048_bot_move(game, name.as_ptr(), 0);
+                assert!(direction >= 0);
+                assert_ne!(game2048_move(game, direction as u32), GAME2048_ERROR);
+            }
+            assert!(game2048_score(game) > 0);
+
+            let size = game2048_serialize(game, ptr::null_mut(), 0);
+            let mut buffer = vec![0 as c_char; size as usize + 1];
+            assert_eq!(
+                game2048_serialize(game, buffer.as_mut_ptr(), buffer.len()),
+                size
+            );
+
+            let copy = game2048_deserialize(buffer.as_ptr(), 0);
+            assert!(!copy.is_null());
+            assert_eq!(get_cells(copy), get_cells(game));
+            assert_eq!(game2048_score(copy), game2048_score(game));
+            assert_eq!(game2048_status(copy), GAME2048_LOST);
+
+            game2048_free(game);
+            game2048_free(copy);
+        }
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        unsafe {
+            assert!(game2048_new(1, 9).is_null());
+            assert_eq!(
+                game2048_move(ptr::null_mut(), GAME2048_LEFT),
+                GAME2048_ERROR
+            );
+            assert_eq!(game2048_status(ptr::null()), GAME2048_ERROR);
+            assert!(game2048_deserialize(c"{}".as_ptr(), 0).is_null());
+
+            let game = game2048_new(1, GAME2048_RULES_WRAP_DIAGONAL);
+            assert_eq!(game2048_move(game, 8), GAME2048_ERROR);
+            assert_eq!(
+                game2048_bot_move(game, c"oracle".as_ptr(), 0),
+                GAME2048_ERROR
+            );
+            assert_ne!(game2048_move(game, GAME2048_DOWN_RIGHT), GAME2048_ERROR);
+            game2048_free(game);
+            game2048_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn committed_header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/game2048.h"));
+        let committed = include_str!("../include/game2048.h");
+        assert!(
+            generated == committed,
+            "include/game2048.h is out of date, rebuild with GAME2048_WRITE_HEADER=1"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+"""Builds the extension module and puts it next to this file, ready to import.
+
+    python3 python/build.py [--debug]
+    python3 -m unittest discover python/tests
+"""
+
+import os
+import shutil
+import subprocess
+import sys
+import sysconfig
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+ROOT = os.path.dirname(HERE)
+
+
+def main():
+    profile = "debug" if "--debug" in sys.argv else "release"
+    command = ["cargo", "build", "-p", "game2048-python"]
+    if profile == "release":
+        command.append("--release")
+    subprocess.run(command, cwd=ROOT, check=True)
+
+    prefix, suffix = {"win32": ("", ".dll"), "darwin": ("lib", ".dylib")}.get(
+        sys.platform, ("lib", ".so")
+    )
+    built = os.path.join(ROOT, "target", profile, prefix + "game2048_python" + suffix)
+    extension = sysconfig.get_config_var("EXT_SUFFIX") or ".so"
+    target = os.path.join(HERE, "game2048" + extension)
+    shutil.copyfile(built, target)
+    print("built", target)
+
+
+if __name__ == "__main__":
+    main()
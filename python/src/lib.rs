@@ -0,0 +1,449 @@
+// Python bindings for the engine, the RL environment and the built in agents. Moves and
+// rules are passed as their notation strings, e.g. "L" or "left", and "classic" or "wrap".
+
+use std::cell::Cell;
+
+use pyo3::buffer::{Element, PyBuffer};
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::{rngs::StdRng, SeedableRng};
+
+use game2048::agent::{self, AGENT_NAMES};
+use game2048::batch;
+use game2048::env::{self, Encoding, InvalidMove, Reward};
+use game2048::grid::{self, GameStatus, Move, Rules};
+
+fn parse_move(notation: &str) -> PyResult<Move> {
+    notation.parse().map_err(PyValueError::new_err)
+}
+
+fn parse_rules(name: &str) -> PyResult<Rules> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+fn get_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn max_tile(grid: &grid::Grid) -> u64 {
+    grid.cells.iter().flatten().copied().max().unwrap_or(0)
+}
+
+// A game with its own random number generator, so seeded games are reproducible
+#[pyclass]
+#[derive(Clone)]
+struct Grid {
+    grid: grid::Grid,
+    rng: StdRng,
+}
+
+#[pymethods]
+impl Grid {
+    #[new]
+    #[pyo3(signature = (seed=None, rules="classic"))]
+    fn new(seed: Option<u64>, rules: &str) -> PyResult<Self> {
+        let mut rng = get_rng(seed);
+        let grid = grid::Grid::new_random_with_rng(parse_rules(rules)?, &mut rng);
+        Ok(Self { grid, rng })
+    }
+
+    // A position in notation, e.g. "2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2"
+    #[staticmethod]
+    #[pyo3(signature = (notation, rules="classic", seed=None))]
+    fn from_notation(notation: &str, rules: &str, seed: Option<u64>) -> PyResult<Self> {
+        let grid: grid::Grid = notation.parse().map_err(PyValueError::new_err)?;
+        Ok(Self {
+            grid: grid::Grid::new_with_rules(grid.cells, parse_rules(rules)?),
+            rng: get_rng(seed),
+        })
+    }
+
+    #[getter]
+    fn cells(&self) -> Vec<Vec<u64>> {
+        self.grid.cells.iter().map(|row| row.to_vec()).collect()
+    }
+
+    #[getter]
+    fn score(&self) -> u64 {
+        self.grid.get_score()
+    }
+
+    #[getter]
+    fn max_tile(&self) -> u64 {
+        max_tile(&self.grid)
+    }
+
+    #[getter]
+    fn rules(&self) -> String {
+        self.grid.get_rules().to_string()
+    }
+
+    fn valid_moves(&self) -> Vec<String> {
+        self.grid
+            .get_valid_moves()
+            .iter()
+            .map(|mov| mov.to_string())
+            .collect()
+    }
+
+    fn is_lost(&self) -> bool {
+        self.grid.has_player_lost()
+    }
+
+    // Plays a move and spawns a tile. Returns "ok", "invalid" or "lost".
+    #[pyo3(name = "move")]
+    fn attempt(&mut self, mov: &str) -> PyResult<&'static str> {
+        let status = self.grid.attempt_with_rng(parse_move(mov)?, &mut self.rng);
+        Ok(match status {
+            GameStatus::Ok => "ok",
+            GameStatus::InvalidMove => "invalid",
+            GameStatus::Lost => "lost",
+        })
+    }
+
+    // The position after a move, before a tile spawns, or None if the move isn't valid
+    fn after_move(&self, mov: &str) -> PyResult<Option<Grid>> {
+        Ok(self.grid.after_move(parse_move(mov)?).map(|grid| Grid {
+            grid,
+            rng: self.rng.clone(),
+        }))
+    }
+
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.grid.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Grid.from_notation(\"{}\", \"{}\")",
+            self.grid,
+            self.grid.get_rules()
+        )
+    }
+}
+
+// One of the built in agents, by name
+#[pyclass(unsendable)]
+struct Agent {
+    name: String,
+    agent: Box<dyn agent::Agent>,
+}
+
+#[pymethods]
+impl Agent {
+    #[new]
+    #[pyo3(signature = (name, seed=0))]
+    fn new(name: &str, seed: u64) -> PyResult<Self> {
+        let agent = agent::agent_from_name(name, seed).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown agent '{}', expected one of {}",
+                name,
+                AGENT_NAMES.join(", ")
+            ))
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            agent,
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, grid: &Grid) -> Option<String> {
+        self.agent.choose(&grid.grid).map(|mov| mov.to_string())
+    }
+
+    // Plays a whole game. Returns the final grid and the moves played.
+    #[pyo3(signature = (seed=0, rules="classic"))]
+    fn play(&mut self, seed: u64, rules: &str) -> PyResult<(Grid, Vec<String>)> {
+        let record = agent::play_game_with_rules(&mut self.agent, parse_rules(rules)?, seed);
+        let moves = record.moves.iter().map(|mov| mov.to_string()).collect();
+        let grid = Grid {
+            grid: record.grid,
+            rng: get_rng(Some(seed)),
+        };
+        Ok((grid, moves))
+    }
+}
+
+// Gym-style environment. Encodings are "raw", "log2" and "onehot", rewards "score",
+// "merges" and "survival", invalid moves "noop", "penalty" and "masked".
+#[pyclass]
+struct Env {
+    env: env::Env,
+}
+
+#[pymethods]
+impl Env {
+    #[new]
+    #[pyo3(signature = (
+        rules="classic",
+        encoding="log2",
+        reward="score",
+        invalid_move="noop",
+        penalty=1.0,
+        planes=18
+    ))]
+    fn new(
+        rules: &str,
+        encoding: &str,
+        reward: &str,
+        invalid_move: &str,
+        penalty: f32,
+        planes: usize,
+    ) -> PyResult<Self> {
+        let encoding = match encoding {
+            "raw" => Encoding::Raw,
+            "log2" => Encoding::Log2,
+            "onehot" => Encoding::OneHot { planes },
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown encoding '{}'",
+                    encoding
+                )))
+            }
+        };
+        let reward = match reward {
+            "score" => Reward::ScoreGain,
+            "merges" => Reward::MergeCount,
+            "survival" => Reward::Survival,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown reward '{}'",
+                    reward
+                )))
+            }
+        };
+        let invalid_move = match invalid_move {
+            "noop" => InvalidMove::NoOp,
+            "penalty" => InvalidMove::Penalty(penalty),
+            "masked" => InvalidMove::Masked,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown invalid move handling '{}'",
+                    invalid_move
+                )))
+            }
+        };
+        let env = env::Env::new(parse_rules(rules)?)
+            .with_encoding(encoding)
+            .with_reward(reward)
+            .with_invalid_move(invalid_move);
+        Ok(Self { env })
+    }
+
+    #[getter]
+    fn actions(&self) -> Vec<String> {
+        self.env
+            .get_actions()
+            .iter()
+            .map(|mov| mov.to_string())
+            .collect()
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        self.env.get_observation_size()
+    }
+
+    #[getter]
+    fn grid(&self) -> Grid {
+        Grid {
+            grid: self.env.get_grid().clone(),
+            rng: get_rng(Some(0)),
+        }
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.env.reset(seed)
+    }
+
+    fn action_mask(&self) -> Vec<bool> {
+        self.env.action_mask()
+    }
+
+    // Returns (observation, reward, done, info)
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: usize,
+    ) -> PyResult<(Vec<f32>, f32, bool, Bound<'py, PyDict>)> {
+        let step = self
+            .env
+            .step(action)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        let info = PyDict::new(py);
+        info.set_item("score", step.info.score)?;
+        info.set_item("max_tile", step.info.max_tile)?;
+        info.set_item("moves", step.info.moves)?;
+        info.set_item("invalid_move", step.info.invalid_move)?;
+        Ok((step.observation, step.reward, step.done, info))
+    }
+}
+
+// The items of a writable contiguous buffer, which must hold exactly `len` of them
+fn get_writable<'a, T: Element>(
+    py: Python<'a>,
+    buffer: &'a PyBuffer<T>,
+    len: usize,
+) -> PyResult<&'a [Cell<T>]> {
+    let items = buffer
+        .as_mut_slice(py)
+        .ok_or_else(|| PyBufferError::new_err("expected a writable contiguous buffer"))?;
+    if items.len() != len {
+        return Err(PyBufferError::new_err(format!(
+            "expected a buffer of {} items, got {}",
+            len,
+            items.len()
+        )));
+    }
+    Ok(items)
+}
+
+// Many classic games stepped together. Actions are indices into ["L", "R", "U", "D"].
+// The properties and `exponents` build new lists on every call. Training loops should
+// allocate arrays once and pass them to the `fill_*` methods, which copy into any
+// writable buffer of the right type and length, e.g. numpy arrays or `array.array`.
+#[pyclass]
+struct GridBatch {
+    batch: batch::GridBatch,
+}
+
+#[pymethods]
+impl GridBatch {
+    #[new]
+    #[pyo3(signature = (size, seed=0))]
+    fn new(size: usize, seed: u64) -> Self {
+        Self {
+            batch: batch::GridBatch::new(size, seed),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.batch.len()
+    }
+
+    fn step(&mut self, actions: Vec<u8>) -> PyResult<()> {
+        if actions.len() != self.batch.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} actions, got {}",
+                self.batch.len(),
+                actions.len()
+            )));
+        }
+        self.batch.step(&actions);
+        Ok(())
+    }
+
+    // log2 of every tile, 16 bytes per game
+    fn exponents(&self) -> Vec<u8> {
+        let mut exponents = vec![0; 16 * self.batch.len()];
+        self.batch.fill_exponents(&mut exponents);
+        exponents
+    }
+
+    // Bit j is set when action j is valid
+    fn action_masks(&self) -> Vec<u8> {
+        let mut masks = vec![0; self.batch.len()];
+        self.batch.fill_action_masks(&mut masks);
+        masks
+    }
+
+    #[getter]
+    fn boards(&self) -> Vec<u64> {
+        self.batch.get_boards().to_vec()
+    }
+
+    #[getter]
+    fn scores(&self) -> Vec<u64> {
+        self.batch.get_scores().to_vec()
+    }
+
+    #[getter]
+    fn rewards(&self) -> Vec<f32> {
+        self.batch.get_rewards().to_vec()
+    }
+
+    #[getter]
+    fn dones(&self) -> Vec<bool> {
+        self.batch.get_dones().to_vec()
+    }
+
+    #[getter]
+    fn final_scores(&self) -> Vec<u64> {
+        self.batch.get_final_scores().to_vec()
+    }
+
+    // uint8, 16 per game
+    fn fill_exponents(&self, py: Python<'_>, out: PyBuffer<u8>) -> PyResult<()> {
+        let items = get_writable(py, &out, 16 * self.batch.len())?;
+        for (game, cells) in items.chunks_exact(16).enumerate() {
+            for (cell, exponent) in cells.iter().zip(self.batch.get_exponents(game)) {
+                cell.set(exponent);
+            }
+        }
+        Ok(())
+    }
+
+    // uint8
+    fn fill_action_masks(&self, py: Python<'_>, out: PyBuffer<u8>) -> PyResult<()> {
+        let items = get_writable(py, &out, self.batch.len())?;
+        for (game, mask) in items.iter().enumerate() {
+            mask.set(self.batch.get_action_mask(game));
+        }
+        Ok(())
+    }
+
+    // uint64
+    fn fill_boards(&self, py: Python<'_>, out: PyBuffer<u64>) -> PyResult<()> {
+        out.copy_from_slice(py, self.batch.get_boards())
+    }
+
+    // uint64
+    fn fill_scores(&self, py: Python<'_>, out: PyBuffer<u64>) -> PyResult<()> {
+        out.copy_from_slice(py, self.batch.get_scores())
+    }
+
+    // float32
+    fn fill_rewards(&self, py: Python<'_>, out: PyBuffer<f32>) -> PyResult<()> {
+        out.copy_from_slice(py, self.batch.get_rewards())
+    }
+
+    // uint8, 1 where the game just ended
+    fn fill_dones(&self, py: Python<'_>, out: PyBuffer<u8>) -> PyResult<()> {
+        let items = get_writable(py, &out, self.batch.len())?;
+        for (item, done) in items.iter().zip(self.batch.get_dones()) {
+            item.set(*done as u8);
+        }
+        Ok(())
+    }
+
+    // uint64
+    fn fill_final_scores(&self, py: Python<'_>, out: PyBuffer<u64>) -> PyResult<()> {
+        out.copy_from_slice(py, self.batch.get_final_scores())
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "game2048")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Grid>()?;
+    module.add_class::<Agent>()?;
+    module.add_class::<Env>()?;
+    module.add_class::<GridBatch>()?;
+    module.add("AGENT_NAMES", AGENT_NAMES.to_vec())?;
+    module.add("RULES", grid::RULE_NAMES.to_vec())?;
+    Ok(())
+}
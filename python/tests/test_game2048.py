@@ -0,0 +1,149 @@
+import array
+import os
+import sys
+import unittest
+
+sys.path.insert(0, os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+
+import game2048  # noqa: E402
+
+
+class GridTest(unittest.TestCase):
+    def test_seeded_games_repeat(self):
+        first = game2048.Grid(seed=4)
+        second = game2048.Grid(seed=4)
+        for move in ["L", "D", "right", "up", "D"]:
+            self.assertEqual(first.move(move), second.move(move))
+        self.assertEqual(first.cells, second.cells)
+        self.assertEqual(first.score, second.score)
+
+    def test_notation(self):
+        grid = game2048.Grid.from_notation("2,2,0,0/0,0,0,0/0,0,0,0/0,0,0,4")
+        self.assertEqual(str(grid), "2,2,0,0/0,0,0,0/0,0,0,0/0,0,0,4")
+        self.assertEqual(grid.max_tile, 4)
+        self.assertIn("L", grid.valid_moves())
+
+        after = grid.after_move("L")
+        self.assertEqual(after.cells[0], [4, 0, 0, 0])
+        self.assertEqual(after.score, 4)
+        self.assertEqual(grid.score, 0)
+
+    def test_invalid_input(self):
+        with self.assertRaises(ValueError):
+            game2048.Grid(rules="hexagonal")
+        with self.assertRaises(ValueError):
+            game2048.Grid(seed=1).move("sideways")
+        with self.assertRaises(ValueError):
+            game2048.Grid.from_notation("3,0,0,0/0,0,0,0/0,0,0,0/0,0,0,0")
+
+    def test_other_rules(self):
+        grid = game2048.Grid(seed=2, rules="wrap-diagonal")
+        self.assertEqual(grid.rules, "wrap-diagonal")
+        self.assertIn(grid.move("UL"), ("ok", "invalid", "lost"))
+
+
+class AgentTest(unittest.TestCase):
+    def test_agents_play_games(self):
+        self.assertIn("expectimax", game2048.AGENT_NAMES)
+        grid, moves = game2048.Agent("corner").play(seed=3)
+        self.assertGreater(len(moves), 10)
+        again, _ = game2048.Agent("corner").play(seed=3)
+        self.assertEqual(grid.cells, again.cells)
+
+    def test_choose(self):
+        grid = game2048.Grid.from_notation("1024,1024,0,0/0,0,0,0/0,0,0,0/0,0,0,2")
+        move = game2048.Agent("greedy", seed=1).choose(grid)
+        self.assertIn(move, ("L", "R"))
+
+    def test_unknown_agent(self):
+        with self.assertRaises(ValueError):
+            game2048.Agent("oracle")
+
+
+class EnvTest(unittest.TestCase):
+    def test_episode(self):
+        env = game2048.Env(encoding="onehot", planes=16)
+        observation = env.reset(5)
+        self.assertEqual(len(observation), env.observation_size)
+        self.assertEqual(len(observation), 256)
+        self.assertEqual(env.actions, ["L", "R", "U", "D"])
+
+        done = False
+        steps = 0
+        while not done:
+            mask = env.action_mask()
+            action = mask.index(True)
+            observation, reward, done, info = env.step(action)
+            self.assertGreaterEqual(reward, 0)
+            self.assertFalse(info["invalid_move"])
+            steps += 1
+        self.assertEqual(info["moves"], steps)
+        self.assertTrue(env.grid.is_lost())
+        with self.assertRaises(ValueError):
+            env.step(0)
+
+    def test_masked_moves(self):
+        env = game2048.Env(invalid_move="masked")
+        for seed in range(100):
+            env.reset(seed)
+            mask = env.action_mask()
+            if not all(mask):
+                with self.assertRaises(ValueError):
+                    env.step(mask.index(False))
+                return
+        self.fail("no start with an invalid move")
+
+    def test_options(self):
+        with self.assertRaises(ValueError):
+            game2048.Env(reward="happiness")
+        env = game2048.Env(encoding="raw", reward="survival", invalid_move="penalty", penalty=3)
+        env.reset(1)
+        mask = env.action_mask()
+        _, reward, _, _ = env.step(mask.index(True))
+        self.assertEqual(reward, 1.0)
+
+
+class GridBatchTest(unittest.TestCase):
+    def test_batch(self):
+        batch = game2048.GridBatch(16, seed=9)
+        self.assertEqual(len(batch), 16)
+        finished = 0
+        for _ in range(2000):
+            masks = batch.action_masks()
+            actions = [(mask & -mask).bit_length() - 1 for mask in masks]
+            batch.step(actions)
+            finished += sum(batch.dones)
+        self.assertGreater(finished, 0)
+        self.assertEqual(len(batch.exponents()), 16 * 16)
+        self.assertEqual(len(batch.rewards), 16)
+
+        with self.assertRaises(ValueError):
+            batch.step([0, 1])
+
+    def test_fill_buffers(self):
+        batch = game2048.GridBatch(4, seed=3)
+        batch.step([0, 1, 2, 3])
+        exponents, masks = bytearray(4 * 16), bytearray(4)
+        batch.fill_exponents(exponents)
+        batch.fill_action_masks(masks)
+        self.assertEqual(bytes(exponents), batch.exponents())
+        self.assertEqual(bytes(masks), batch.action_masks())
+        boards, rewards = array.array("Q", [0] * 4), array.array("f", [0.0] * 4)
+        batch.fill_boards(boards)
+        batch.fill_rewards(rewards)
+        self.assertEqual(list(boards), batch.boards)
+        self.assertEqual(list(rewards), batch.rewards)
+        dones = bytearray(4)
+        batch.fill_dones(dones)
+        self.assertEqual([bool(done) for done in dones], batch.dones)
+
+        with self.assertRaises(BufferError):
+            batch.fill_scores(array.array("Q", [0] * 3))
+        with self.assertRaises(BufferError):
+            batch.fill_exponents(bytearray(4 * 16 - 1))
+        with self.assertRaises(BufferError):
+            batch.fill_dones(bytes(4))
+
+
+if __name__ == "__main__":
+    unittest.main()
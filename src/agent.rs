@@ -0,0 +1,255 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::grid::{GameStatus, Grid, Move, Rules};
+use crate::heuristic::Heuristic;
+use crate::monte_carlo::{MonteCarlo, RolloutPolicy};
+use crate::ntuple::NTupleNetwork;
+use crate::solver::Expectimax;
+
+// Names of the agents `agent_from_name` can create
+pub const AGENT_NAMES: [&str; 6] = [
+    "random",
+    "cyclic",
+    "greedy",
+    "corner",
+    "expectimax",
+    "montecarlo",
+];
+
+const CYCLE: [Move; 4] = [Move::Down, Move::Left, Move::Down, Move::Right];
+// Keeps the largest tile in the bottom left corner, only moving up when nothing else works
+const CORNER_PREFERENCE: [Move; 4] = [Move::Down, Move::Left, Move::Right, Move::Up];
+
+// Anything that can play the game. Returns None when it has no move to make.
+pub trait Agent {
+    fn choose(&mut self, grid: &Grid) -> Option<Move>;
+}
+
+impl<A: Agent + ?Sized> Agent for Box<A> {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.as_mut().choose(grid)
+    }
+}
+
+// A built-in agent with its default settings, seeded if it uses randomness
+pub fn agent_from_name(name: &str, seed: u64) -> Option<Box<dyn Agent>> {
+    let agent: Box<dyn Agent> = match name {
+        "random" => Box::new(RandomAgent::new(seed)),
+        "cyclic" => Box::new(CyclicAgent::default()),
+        "greedy" => Box::new(GreedyAgent::new(seed)),
+        "corner" => Box::new(CornerAgent),
+        "expectimax" => Box::new(Expectimax::default()),
+        "montecarlo" => Box::new(MonteCarlo::new(seed)),
+        _ => return None,
+    };
+    Some(agent)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub grid: Grid,
+    pub moves: Vec<Move>,
+}
+
+pub fn play_game<A: Agent + ?Sized>(agent: &mut A, seed: u64) -> GameRecord {
+    play_game_with_rules(agent, Rules::default(), seed)
+}
+
+// Plays until the game is lost or the agent stops, either by returning None or by picking
+// a move that isn't valid. The seed decides every spawn, so agents that don't use
+// randomness always play the same game.
+pub fn play_game_with_rules<A: Agent + ?Sized>(
+    agent: &mut A,
+    rules: Rules,
+    seed: u64,
+) -> GameRecord {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut grid = Grid::new_random_with_rng(rules, &mut rng);
+    let mut moves = Vec::new();
+    while let Some(mov) = agent.choose(&grid) {
+        match grid.attempt_with_rng(mov, &mut rng) {
+            GameStatus::InvalidMove => break,
+            GameStatus::Lost => {
+                moves.push(mov);
+                break;
+            }
+            GameStatus::Ok => moves.push(mov),
+        }
+    }
+    GameRecord { grid, moves }
+}
+
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        grid.get_valid_moves().choose(&mut self.rng).copied()
+    }
+}
+
+// Repeats Down, Left, Down, Right, skipping moves that aren't valid
+#[derive(Default)]
+pub struct CyclicAgent {
+    position: usize,
+}
+
+impl Agent for CyclicAgent {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        let valid_moves = grid.get_valid_moves();
+        for _ in 0..CYCLE.len() {
+            let mov = CYCLE[self.position];
+            self.position = (self.position + 1) % CYCLE.len();
+            if valid_moves.contains(&mov) {
+                return Some(mov);
+            }
+        }
+        valid_moves.first().copied()
+    }
+}
+
+// Takes the move with the biggest immediate score gain, ties broken at random
+pub struct GreedyAgent {
+    rng: StdRng,
+}
+
+impl GreedyAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+// Plays like the greedy playouts of `MonteCarlo`, so the two can't drift apart
+impl Agent for GreedyAgent {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        RolloutPolicy::Greedy.choose(grid, &mut self.rng)
+    }
+}
+
+#[derive(Default)]
+pub struct CornerAgent;
+
+impl Agent for CornerAgent {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        let valid_moves = grid.get_valid_moves();
+        CORNER_PREFERENCE
+            .iter()
+            .find(|mov| valid_moves.contains(mov))
+            .or_else(|| valid_moves.first())
+            .copied()
+    }
+}
+
+// The parallel searches pick the same moves, only sooner. The bounds are the same with
+// and without the feature, so turning it on doesn't take any agents away.
+impl<H: Heuristic + Sync> Agent for Expectimax<H> {
+    #[cfg(not(feature = "parallel"))]
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.best_move(grid)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.analyze_parallel(grid).best_move
+    }
+}
+
+impl Agent for MonteCarlo {
+    #[cfg(not(feature = "parallel"))]
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.best_move(grid)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.best_move_parallel(grid)
+    }
+}
+
+impl Agent for NTupleNetwork {
+    fn choose(&mut self, grid: &Grid) -> Option<Move> {
+        self.best_move(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{play_game, Agent, CornerAgent, CyclicAgent, GreedyAgent, RandomAgent};
+    use crate::grid::{Grid, Move};
+    use crate::solver::Expectimax;
+
+    #[test]
+    fn same_seeds_play_the_same_game() {
+        let first = play_game(&mut RandomAgent::new(1), 42);
+        let second = play_game(&mut RandomAgent::new(1), 42);
+        assert_eq!(first, second);
+        assert!(first.grid.has_player_lost());
+        assert!(!first.moves.is_empty());
+    }
+
+    #[test]
+    fn cyclic_agent_repeats_its_pattern() {
+        let row1 = [0, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 2, 0, 0];
+        let row4 = [0, 4, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let mut agent = CyclicAgent::default();
+        // Down isn't valid, so the cycle moves on to Left and then Right
+        assert_eq!(agent.choose(&grid), Some(Move::Left));
+        assert_eq!(agent.choose(&grid), Some(Move::Right));
+        assert_eq!(agent.choose(&grid), Some(Move::Left));
+    }
+
+    #[test]
+    fn greedy_agent_takes_the_biggest_merge() {
+        let row1 = [2, 2, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 64];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let mov = GreedyAgent::new(5).choose(&grid);
+        assert!(mov == Some(Move::Left) || mov == Some(Move::Right));
+    }
+
+    #[test]
+    fn corner_agent_avoids_moving_up() {
+        let row1 = [0, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [8, 4, 2, 16];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        assert_eq!(CornerAgent.choose(&grid), Some(Move::Up));
+        let mut grid = grid;
+        grid.insert_cell(2, 0, 2);
+        assert_eq!(CornerAgent.choose(&grid), Some(Move::Right));
+    }
+
+    #[test]
+    fn bots_play_through_the_same_interface() {
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(CyclicAgent::default()),
+            Box::new(CornerAgent),
+            Box::new(GreedyAgent::new(3)),
+            Box::new(Expectimax::default().with_depth(1)),
+        ];
+        for agent in agents.iter_mut() {
+            let record = play_game(agent.as_mut(), 8);
+            assert!(record.grid.has_player_lost());
+        }
+    }
+}
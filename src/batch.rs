@@ -0,0 +1,191 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::bitboard::Bitboard;
+use crate::grid::MOVES;
+
+// Many independent classic games stepped together, for reinforcement learning. Boards are
+// packed `Bitboard`s and every result lives in a flat buffer with one entry per game,
+// so stepping allocates nothing and the buffers can be handed out as slices.
+//
+// Actions are indices into `MOVES`. A move that can't be played, or an unknown action,
+// leaves the board as it is with a reward of 0. Games that end are started again right
+// away, and their final score is kept in `get_final_scores` for that step.
+pub struct GridBatch {
+    boards: Vec<u64>,
+    scores: Vec<u64>,
+    rewards: Vec<f32>,
+    dones: Vec<bool>,
+    final_scores: Vec<u64>,
+    // Every game has its own generator, so a game plays the same whatever the batch size
+    rngs: Vec<StdRng>,
+}
+
+impl GridBatch {
+    // Game i is seeded with `seed + i`
+    pub fn new(size: usize, seed: u64) -> Self {
+        let mut batch = Self {
+            boards: vec![0; size],
+            scores: vec![0; size],
+            rewards: vec![0.0; size],
+            dones: vec![false; size],
+            final_scores: vec![0; size],
+            rngs: (0..size)
+                .map(|i| StdRng::seed_from_u64(seed.wrapping_add(i as u64)))
+                .collect(),
+        };
+        for i in 0..size {
+            batch.reset_game(i);
+        }
+        batch
+    }
+
+    pub fn len(&self) -> usize {
+        self.boards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
+
+    fn reset_game(&mut self, i: usize) {
+        let rng = &mut self.rngs[i];
+        self.boards[i] = Bitboard(0).spawn(rng).spawn(rng).0;
+        self.scores[i] = 0;
+    }
+
+    // Plays one action in every game. Panics if there isn't exactly one action per game.
+    pub fn step(&mut self, actions: &[u8]) {
+        assert_eq!(actions.len(), self.len(), "one action per game is needed");
+        for (i, action) in actions.iter().enumerate() {
+            self.dones[i] = false;
+            self.final_scores[i] = 0;
+            let after = MOVES
+                .get(*action as usize)
+                .and_then(|mov| Bitboard(self.boards[i]).after_move(*mov));
+            let (board, gain) = match after {
+                Some(after) => after,
+                None => {
+                    self.rewards[i] = 0.0;
+                    continue;
+                }
+            };
+            let board = board.spawn(&mut self.rngs[i]);
+            self.boards[i] = board.0;
+            self.scores[i] += gain as u64;
+            self.rewards[i] = gain as f32;
+            if board.is_lost() {
+                self.dones[i] = true;
+                self.final_scores[i] = self.scores[i];
+                self.reset_game(i);
+            }
+        }
+    }
+
+    // Bit mask of the valid actions of a game, bit j for `MOVES[j]`
+    pub fn get_action_mask(&self, game: usize) -> u8 {
+        let board = Bitboard(self.boards[game]);
+        MOVES
+            .iter()
+            .enumerate()
+            .filter(|(_, mov)| board.after_move(**mov).is_some())
+            .fold(0, |mask, (j, _)| mask | 1 << j)
+    }
+
+    // log2 of every tile of a game, row by row, 0 for empty cells
+    pub fn get_exponents(&self, game: usize) -> [u8; 16] {
+        let board = self.boards[game];
+        std::array::from_fn(|i| ((board >> (4 * i)) & 0xF) as u8)
+    }
+
+    // Bit masks of the valid actions of every game
+    pub fn fill_action_masks(&self, masks: &mut [u8]) {
+        for (game, mask) in masks.iter_mut().enumerate().take(self.len()) {
+            *mask = self.get_action_mask(game);
+        }
+    }
+
+    // The exponents of every game, 16 per game
+    pub fn fill_exponents(&self, exponents: &mut [u8]) {
+        for (game, cells) in exponents.chunks_exact_mut(16).enumerate().take(self.len()) {
+            cells.copy_from_slice(&self.get_exponents(game));
+        }
+    }
+
+    pub fn get_boards(&self) -> &[u64] {
+        &self.boards
+    }
+
+    pub fn get_scores(&self) -> &[u64] {
+        &self.scores
+    }
+
+    pub fn get_rewards(&self) -> &[f32] {
+        &self.rewards
+    }
+
+    pub fn get_dones(&self) -> &[bool] {
+        &self.dones
+    }
+
+    pub fn get_final_scores(&self) -> &[u64] {
+        &self.final_scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridBatch;
+    use crate::bitboard::Bitboard;
+
+    #[test]
+    fn games_are_independent_of_the_batch_size() {
+        let mut small = GridBatch::new(2, 10);
+        let mut large = GridBatch::new(50, 10);
+        for step in 0..200 {
+            let action = (step % 4) as u8;
+            small.step(&[action; 2]);
+            large.step(&[action; 50]);
+            assert_eq!(small.get_boards(), &large.get_boards()[..2]);
+            assert_eq!(small.get_rewards(), &large.get_rewards()[..2]);
+        }
+    }
+
+    #[test]
+    fn finished_games_start_again() {
+        let mut batch = GridBatch::new(8, 3);
+        let mut finished = 0;
+        let mut masks = [0; 8];
+        for _ in 0..5000 {
+            // Always play the first valid move
+            batch.fill_action_masks(&mut masks);
+            let actions: Vec<u8> = masks
+                .iter()
+                .map(|mask| mask.trailing_zeros() as u8)
+                .collect();
+            batch.step(&actions);
+            for (i, done) in batch.get_dones().iter().enumerate() {
+                if *done {
+                    finished += 1;
+                    assert!(batch.get_final_scores()[i] > 0);
+                    assert_eq!(batch.get_scores()[i], 0);
+                    assert_eq!(Bitboard(batch.get_boards()[i]).count_empty(), 14);
+                }
+            }
+        }
+        assert!(finished > 0);
+    }
+
+    #[test]
+    fn invalid_actions_change_nothing() {
+        let mut batch = GridBatch::new(3, 1);
+        let boards = batch.get_boards().to_vec();
+        batch.step(&[9, 9, 9]);
+        assert_eq!(batch.get_boards(), &boards[..]);
+        assert_eq!(batch.get_rewards(), &[0.0; 3]);
+
+        let mut exponents = [0; 48];
+        batch.fill_exponents(&mut exponents);
+        let tiles = exponents.iter().filter(|exponent| **exponent > 0).count();
+        assert_eq!(tiles, 6);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::agent::{play_game_with_rules, Agent, GameRecord};
+use crate::grid::Rules;
+use crate::solver::now_ms;
+
+// Tiles whose reach rate is reported
+pub const TILE_MILESTONES: [u64; 5] = [512, 1024, 2048, 4096, 8192];
+const PERCENTILES: [u32; 5] = [10, 25, 75, 90, 99];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub agent: String,
+    pub rules: String,
+    pub games: usize,
+    pub mean_score: f64,
+    pub median_score: u64,
+    pub max_score: u64,
+    // Score at every percentile, nearest rank
+    pub percentiles: BTreeMap<u32, u64>,
+    // Fraction of games that reached every milestone tile
+    pub tile_rates: BTreeMap<u64, f64>,
+    pub mean_moves: f64,
+    pub moves_per_second: f64,
+}
+
+// Plays the same seeded games with every agent, so the agents face the same spawns as
+// long as they make the same moves.
+pub struct Benchmark {
+    games: usize,
+    seed: u64,
+}
+
+impl Benchmark {
+    pub fn new(games: usize, seed: u64) -> Self {
+        Self {
+            games: games.max(1),
+            seed,
+        }
+    }
+
+    // Game i is played with seed + i, by a fresh agent created with that seed
+    pub fn run<A, F>(&self, name: &str, rules: Rules, mut make_agent: F) -> Report
+    where
+        A: Agent,
+        F: FnMut(u64) -> A,
+    {
+        let start = now_ms();
+        let records: Vec<GameRecord> = (0..self.games as u64)
+            .map(|i| {
+                let seed = self.seed.wrapping_add(i);
+                play_game_with_rules(&mut make_agent(seed), rules, seed)
+            })
+            .collect();
+        let seconds = (now_ms() - start) / 1000.0;
+        summarize(name, rules, &records, seconds)
+    }
+}
+
+pub fn summarize(name: &str, rules: Rules, records: &[GameRecord], seconds: f64) -> Report {
+    let games = records.len().max(1) as f64;
+    let mut scores: Vec<u64> = records
+        .iter()
+        .map(|record| record.grid.get_score())
+        .collect();
+    scores.sort_unstable();
+    let total_moves: usize = records.iter().map(|record| record.moves.len()).sum();
+    let max_tiles: Vec<u64> = records
+        .iter()
+        .map(|record| {
+            record
+                .grid
+                .cells
+                .iter()
+                .flatten()
+                .copied()
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    Report {
+        agent: name.to_string(),
+        rules: rules.to_string(),
+        games: records.len(),
+        mean_score: scores.iter().sum::<u64>() as f64 / games,
+        median_score: percentile(&scores, 50),
+        max_score: scores.last().copied().unwrap_or(0),
+        percentiles: PERCENTILES
+            .iter()
+            .map(|p| (*p, percentile(&scores, *p)))
+            .collect(),
+        tile_rates: TILE_MILESTONES
+            .iter()
+            .map(|tile| {
+                let reached = max_tiles.iter().filter(|max| *max >= tile).count();
+                (*tile, reached as f64 / games)
+            })
+            .collect(),
+        mean_moves: total_moves as f64 / games,
+        moves_per_second: if seconds > 0.0 {
+            total_moves as f64 / seconds
+        } else {
+            0.0
+        },
+    }
+}
+
+// Nearest rank percentile of sorted values
+fn percentile(sorted: &[u64], p: u32) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p as usize * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+// One row per report, for comparing agents and rules side by side
+pub fn format_table(reports: &[Report]) -> String {
+    let mut table = format!(
+        "{:<12} {:<14} {:>6} {:>9} {:>8} {:>8} {:>8}",
+        "agent", "rules", "games", "mean", "median", "p10", "p90"
+    );
+    for tile in TILE_MILESTONES {
+        write!(table, " {:>6}", tile).unwrap();
+    }
+    write!(table, " {:>8} {:>10}", "moves", "moves/s").unwrap();
+
+    for report in reports {
+        write!(
+            table,
+            "\n{:<12} {:<14} {:>6} {:>9.0} {:>8} {:>8} {:>8}",
+            report.agent,
+            report.rules,
+            report.games,
+            report.mean_score,
+            report.median_score,
+            report.percentiles.get(&10).unwrap_or(&0),
+            report.percentiles.get(&90).unwrap_or(&0),
+        )
+        .unwrap();
+        for tile in TILE_MILESTONES {
+            let rate = report.tile_rates.get(&tile).unwrap_or(&0.0);
+            write!(table, " {:>5.1}%", rate * 100.0).unwrap();
+        }
+        write!(
+            table,
+            " {:>8.0} {:>10.0}",
+            report.mean_moves, report.moves_per_second
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_table, percentile, summarize, Benchmark};
+    use crate::agent::{CornerAgent, GameRecord, RandomAgent};
+    use crate::grid::{Grid, Move, Rules};
+
+    // A game whose score and largest tile are both `tile`
+    fn record(tile: u64, moves: usize) -> GameRecord {
+        let row1 = [tile / 2, tile / 2, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        grid.attempt(Move::Left);
+        GameRecord {
+            grid,
+            moves: vec![Move::Left; moves],
+        }
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let sorted = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
+        assert_eq!(percentile(&sorted, 10), 10);
+        assert_eq!(percentile(&sorted, 50), 50);
+        assert_eq!(percentile(&sorted, 99), 100);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn summary_counts_scores_tiles_and_moves() {
+        let records = [record(512, 100), record(2048, 300), record(1024, 200)];
+        let report = summarize("test", Rules::default(), &records, 2.0);
+
+        assert_eq!(report.games, 3);
+        assert_eq!(report.mean_score, (512.0 + 2048.0 + 1024.0) / 3.0);
+        assert_eq!(report.median_score, 1024);
+        assert_eq!(report.max_score, 2048);
+        assert_eq!(report.tile_rates[&512], 1.0);
+        assert_eq!(report.tile_rates[&2048], 1.0 / 3.0);
+        assert_eq!(report.tile_rates[&4096], 0.0);
+        assert_eq!(report.mean_moves, 200.0);
+        assert_eq!(report.moves_per_second, 300.0);
+    }
+
+    #[test]
+    fn runs_compare_agents_on_the_same_games() {
+        let benchmark = Benchmark::new(3, 10);
+        let corner = benchmark.run("corner", Rules::default(), |_| CornerAgent);
+        let random = benchmark.run("random", Rules::default(), RandomAgent::new);
+        assert_eq!(corner.games, 3);
+
+        // Deterministic agents play the same games every run
+        let again = benchmark.run("corner", Rules::default(), |_| CornerAgent);
+        assert_eq!(corner.mean_score, again.mean_score);
+
+        let table = format_table(&[corner, random]);
+        assert_eq!(table.lines().count(), 3);
+        assert!(table.contains("corner") && table.contains("classic"));
+    }
+
+    #[test]
+    fn reports_serialize_to_json() {
+        let report = summarize("test", Rules::default(), &[record(512, 10)], 1.0);
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"agent\":\"test\""));
+        assert!(json.contains("\"tile_rates\":{\"512\":1.0"));
+    }
+}
@@ -0,0 +1,99 @@
+// Plays seeded games with every combination of the given agents and rules, and prints a
+// table of the results, or JSON with --json.
+//
+//     bench --games 100 --agents corner,expectimax --rules classic,wrap
+//
+// An n-tuple network is played with `ntuple=<weight file>` as the agent name.
+
+use std::process::exit;
+
+use clap::Parser;
+
+use game2048::agent::{agent_from_name, AGENT_NAMES};
+use game2048::benchmark::{format_table, Benchmark};
+use game2048::grid::Rules;
+use game2048::ntuple::NTupleNetwork;
+
+#[derive(Parser)]
+#[command(name = "bench", about = "Compare agents over seeded games")]
+struct Options {
+    /// Games per agent and rules
+    #[arg(long, default_value_t = 100)]
+    games: usize,
+    /// Seed of the first game, the others follow it
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Agents to compare, separated by commas, ntuple=<weight file> plays an n-tuple network
+    #[arg(long, default_value = "corner,greedy", value_delimiter = ',')]
+    agents: Vec<String>,
+    /// Rules to play every agent with, separated by commas
+    #[arg(long, default_value = "classic", value_delimiter = ',')]
+    rules: Vec<Rules>,
+    /// Print JSON instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+fn fail(error: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", error);
+    exit(1);
+}
+
+fn main() {
+    let options = Options::parse();
+    let benchmark = Benchmark::new(options.games, options.seed);
+
+    // Checks every agent and loads the networks before the first game
+    let agents: Vec<(&String, Option<NTupleNetwork>)> = options
+        .agents
+        .iter()
+        .map(|name| {
+            let network = match name.strip_prefix("ntuple=") {
+                Some(path) => {
+                    // The networks are trained on the classic rules only
+                    if let Some(rules) = options
+                        .rules
+                        .iter()
+                        .find(|rules| **rules != Rules::default())
+                    {
+                        fail(format!(
+                            "n-tuple networks only play the classic rules, not {}",
+                            rules
+                        ));
+                    }
+                    Some(
+                        NTupleNetwork::load_from_file(path)
+                            .unwrap_or_else(|error| fail(format!("{}: {}", path, error))),
+                    )
+                }
+                None if AGENT_NAMES.contains(&name.as_str()) => None,
+                None => fail(format!(
+                    "unknown agent '{}', expected one of {}, ntuple=<weight file>",
+                    name,
+                    AGENT_NAMES.join(", ")
+                )),
+            };
+            (name, network)
+        })
+        .collect();
+
+    let mut reports = Vec::new();
+    for (name, network) in agents.iter() {
+        for rules in options.rules.iter() {
+            let report = match network {
+                Some(network) => benchmark.run(name, *rules, |_| network.clone()),
+                None => benchmark.run(name, *rules, |seed| agent_from_name(name, seed).unwrap()),
+            };
+            if !options.json {
+                eprintln!("finished {} with {} rules", name, rules);
+            }
+            reports.push(report);
+        }
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    } else {
+        println!("{}", format_table(&reports));
+    }
+}
@@ -0,0 +1,245 @@
+// Plays the game in the terminal, with the colours of the web app.
+//
+//     game2048-tui [--seed S] [--rules classic|wrap|diagonal|wrap-diagonal]
+//
+// Move with the arrow keys, WASD or hjkl, and diagonally with 7, 9, 1 and 3 when the rules
+// allow it. u or backspace undoes a move, n starts a new game and q quits.
+
+use std::io::{self, Write};
+use std::process::exit;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{self, Color, Stylize};
+use crossterm::{cursor, execute, queue, terminal};
+use rand::{rngs::StdRng, SeedableRng};
+
+use game2048::grid::{GameStatus, Grid, Move, Rules};
+use game2048::palette::{get_color_for_cell, get_color_for_text, to_rgb, BOARD_COLOR};
+
+const USAGE: &str = "usage: game2048-tui [--seed S] [--rules classic|wrap|diagonal|wrap-diagonal]";
+const HELP: &str = "arrows/wasd/hjkl move, u undo, n new game, q quit";
+// Size of a tile in characters
+const TILE_WIDTH: usize = 8;
+const TILE_HEIGHT: usize = 3;
+
+struct Options {
+    seed: Option<u64>,
+    rules: Rules,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        rules: Rules::default(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--seed" => options.seed = Some(value()?.parse().map_err(|_| "invalid seed")?),
+            "--rules" => options.rules = value()?.parse()?,
+            "--help" | "-h" => {
+                println!("{}\n{}", USAGE, HELP);
+                exit(0);
+            }
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
+enum Action {
+    Move(Move),
+    Undo,
+    NewGame,
+    Quit,
+}
+
+fn get_action(key: KeyEvent, rules: Rules) -> Option<Action> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        return Some(Action::Quit);
+    }
+    let mov = match key.code {
+        KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h') => Move::Left,
+        KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l') => Move::Right,
+        KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k') => Move::Up,
+        KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j') => Move::Down,
+        KeyCode::Char('7') if rules.diagonal_moves => Move::UpLeft,
+        KeyCode::Char('9') if rules.diagonal_moves => Move::UpRight,
+        KeyCode::Char('1') if rules.diagonal_moves => Move::DownLeft,
+        KeyCode::Char('3') if rules.diagonal_moves => Move::DownRight,
+        KeyCode::Char('u') | KeyCode::Backspace => return Some(Action::Undo),
+        KeyCode::Char('n') | KeyCode::Char('r') => return Some(Action::NewGame),
+        KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+        _ => return None,
+    };
+    Some(Action::Move(mov))
+}
+
+fn get_color(color: &str) -> Color {
+    let background = to_rgb(BOARD_COLOR, (0, 0, 0)).unwrap();
+    let (r, g, b) = to_rgb(color, background).unwrap_or(background);
+    Color::Rgb { r, g, b }
+}
+
+struct Game {
+    grid: Grid,
+    rng: StdRng,
+    // Positions before every move, for undo
+    history: Vec<Grid>,
+    rules: Rules,
+}
+
+impl Game {
+    fn new(rules: Rules, seed: Option<u64>) -> Self {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            grid: Grid::new_random_with_rng(rules, &mut rng),
+            rng,
+            history: Vec::new(),
+            rules,
+        }
+    }
+
+    fn play(&mut self, mov: Move) {
+        let before = self.grid.clone();
+        if self.grid.attempt_with_rng(mov, &mut self.rng) != GameStatus::InvalidMove {
+            self.history.push(before);
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(grid) = self.history.pop() {
+            self.grid = grid;
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.grid = Grid::new_random_with_rng(self.rules, &mut self.rng);
+        self.history.clear();
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(
+            out,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        let best = self.grid.cells.iter().flatten().max().copied().unwrap_or(0);
+        queue!(
+            out,
+            style::Print(format!(
+                "Score {}   Best tile {}   Moves {}   Rules {}\r\n\r\n",
+                self.grid.get_score(),
+                best,
+                self.history.len(),
+                self.rules
+            ))
+        )?;
+
+        let board = get_color(BOARD_COLOR);
+        let width = 4 * TILE_WIDTH + 5;
+        for row in self.grid.cells.iter() {
+            queue!(
+                out,
+                style::PrintStyledContent(" ".repeat(width).on(board)),
+                style::Print("\r\n")
+            )?;
+            for line in 0..TILE_HEIGHT {
+                queue!(out, style::PrintStyledContent(" ".on(board)))?;
+                for cell in row.iter() {
+                    let text = if line == TILE_HEIGHT / 2 && *cell != 0 {
+                        format!("{:^width$}", cell, width = TILE_WIDTH)
+                    } else {
+                        " ".repeat(TILE_WIDTH)
+                    };
+                    let tile = text
+                        .with(get_color(get_color_for_text(*cell)))
+                        .on(get_color(get_color_for_cell(*cell)))
+                        .bold();
+                    queue!(
+                        out,
+                        style::PrintStyledContent(tile),
+                        style::PrintStyledContent(" ".on(board))
+                    )?;
+                }
+                queue!(out, style::Print("\r\n"))?;
+            }
+        }
+        queue!(
+            out,
+            style::PrintStyledContent(" ".repeat(width).on(board)),
+            style::Print("\r\n\r\n")
+        )?;
+
+        if self.grid.has_player_lost() {
+            let text = format!(
+                " Game over with {} points. n: new game, u: undo, q: quit ",
+                self.grid.get_score()
+            );
+            queue!(
+                out,
+                style::PrintStyledContent(text.white().on(get_color("#f65e3b")).bold())
+            )?;
+        } else {
+            queue!(out, style::Print(HELP))?;
+        }
+        queue!(out, style::Print("\r\n"))?;
+        out.flush()
+    }
+}
+
+// Puts the terminal back the way it was, even after a panic
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let _terminal = RawTerminal::enter()?;
+    let mut out = io::stdout();
+    let mut game = Game::new(options.rules, options.seed);
+    loop {
+        game.draw(&mut out)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        match get_action(key, game.rules) {
+            Some(Action::Move(mov)) => game.play(mov),
+            Some(Action::Undo) => game.undo(),
+            Some(Action::NewGame) => game.new_game(),
+            Some(Action::Quit) => return Ok(()),
+            None => {}
+        }
+    }
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        exit(2);
+    });
+    if let Err(error) = run(options) {
+        eprintln!("terminal error: {}", error);
+        exit(1);
+    }
+}
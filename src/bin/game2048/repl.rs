@@ -0,0 +1,386 @@
+// Commands against a live position, to debug reported positions and tune the heuristic
+// without writing throwaway tests. Moves don't spawn tiles, use `spawn` to place them.
+
+use std::fs;
+
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use game2048::grid::{Grid, Rules};
+use game2048::heuristic::{Heuristic, WeightedHeuristic};
+use game2048::solver::Expectimax;
+
+use crate::{format_analysis, format_board};
+
+const HELP: &str = "\
+show                    print the position
+set ROW COL VALUE       put a tile, 0 clears the cell
+spawn ROW COL VALUE     spawn a 2 or a 4 on an empty cell
+move DIRECTION          play a move without spawning, e.g. move left
+undo                    take back the last change
+eval                    the heuristic value, feature by feature
+hint [DEPTH]            expectimax evaluation of every move
+position NOTATION       replace the position, e.g. 2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2
+save FILE               write the position in notation
+load FILE               read a position written by save
+quit";
+
+const COMMANDS: [&str; 12] = [
+    "show", "set", "spawn", "move", "undo", "eval", "hint", "position", "save", "load", "help",
+    "quit",
+];
+const DIRECTIONS: [&str; 8] = [
+    "left",
+    "right",
+    "up",
+    "down",
+    "up-left",
+    "up-right",
+    "down-left",
+    "down-right",
+];
+const DEFAULT_HINT_DEPTH: usize = 3;
+const HISTORY_FILE: &str = ".game2048_history";
+
+pub struct Session {
+    grid: Grid,
+    // Positions before every change, for undo
+    history: Vec<Grid>,
+    heuristic: WeightedHeuristic,
+}
+
+impl Session {
+    pub fn new(grid: Grid, heuristic: WeightedHeuristic) -> Self {
+        Self {
+            grid,
+            history: Vec::new(),
+            heuristic,
+        }
+    }
+
+    fn change(&mut self, grid: Grid) -> Result<String, String> {
+        self.history.push(std::mem::replace(&mut self.grid, grid));
+        Ok(self.show())
+    }
+
+    fn show(&self) -> String {
+        format!(
+            "{}score {}, {} rules\n{}",
+            format_board(&self.grid),
+            self.grid.get_score(),
+            self.grid.get_rules(),
+            self.grid
+        )
+    }
+
+    fn eval(&self) -> String {
+        let mut lines: Vec<String> = self
+            .heuristic
+            .explain(&self.grid)
+            .iter()
+            .map(|(feature, value)| format!("{:<20}{:>12.1}", feature.get_name(), value))
+            .collect();
+        lines.push(format!(
+            "{:<20}{:>12.1}",
+            "total",
+            self.heuristic.evaluate(&self.grid)
+        ));
+        lines.join("\n")
+    }
+
+    // Runs one command line and returns what to print
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(HELP.to_string()),
+            ["show"] => Ok(self.show()),
+            ["set", row, column, value] => {
+                let (row, column) = parse_cell(row, column)?;
+                let value = parse_tile(value)?;
+                let mut grid = self.grid.clone();
+                grid.cells[row][column] = value;
+                self.change(grid)
+            }
+            ["spawn", row, column, value] => {
+                let (row, column) = parse_cell(row, column)?;
+                let value = match *value {
+                    "2" => 2,
+                    "4" => 4,
+                    _ => return Err(format!("only 2 and 4 spawn, not '{}'", value)),
+                };
+                let mut grid = self.grid.clone();
+                if !grid.insert_cell(row, column, value) {
+                    return Err(format!("{} {} isn't empty", row, column));
+                }
+                self.change(grid)
+            }
+            ["move", direction] => {
+                let mov = direction.parse()?;
+                match self.grid.after_move(mov) {
+                    Some(grid) => self.change(grid),
+                    None => Err(format!("{} doesn't move anything", mov)),
+                }
+            }
+            ["undo"] => match self.history.pop() {
+                Some(grid) => {
+                    self.grid = grid;
+                    Ok(self.show())
+                }
+                None => Err("nothing to undo".to_string()),
+            },
+            ["eval"] => Ok(self.eval()),
+            ["hint", depth @ ..] if depth.len() <= 1 => {
+                let depth = match depth.first() {
+                    Some(depth) => depth
+                        .parse()
+                        .map_err(|_| format!("invalid depth '{}'", depth))?,
+                    None => DEFAULT_HINT_DEPTH,
+                };
+                let analysis = Expectimax::new(self.heuristic.clone())
+                    .with_depth(depth)
+                    .analyze(&self.grid);
+                Ok(format_analysis(&analysis))
+            }
+            ["position", notation] => {
+                let grid: Grid = notation.parse()?;
+                self.change(Grid::new_with_rules(grid.cells, self.grid.get_rules()))
+            }
+            ["save", path] => {
+                fs::write(path, format!("{}\n", self.grid))
+                    .map_err(|error| format!("couldn't write {}: {}", path, error))?;
+                Ok(format!("saved {}", path))
+            }
+            ["load", path] => {
+                let notation = fs::read_to_string(path)
+                    .map_err(|error| format!("couldn't read {}: {}", path, error))?;
+                let grid: Grid = notation.parse()?;
+                self.change(Grid::new_with_rules(grid.cells, self.grid.get_rules()))
+            }
+            [command, ..] if COMMANDS.contains(command) => {
+                Err(format!("wrong arguments for {}, see help", command))
+            }
+            [command, ..] => Err(format!("unknown command '{}', see help", command)),
+        }
+    }
+}
+
+fn parse_cell(row: &str, column: &str) -> Result<(usize, usize), String> {
+    let parse = |index: &str| match index.parse() {
+        Ok(index) if index < 4 => Ok(index),
+        _ => Err(format!(
+            "invalid row or column '{}', expected 0 to 3",
+            index
+        )),
+    };
+    Ok((parse(row)?, parse(column)?))
+}
+
+fn parse_tile(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(value) if value == 0 || (value > 1 && value.is_power_of_two()) => Ok(value),
+        _ => Err(format!("invalid tile '{}'", value)),
+    }
+}
+
+// Completes command names, directions after `move` and paths after `save` and `load`
+struct CommandHelper {
+    rules: Rules,
+    filenames: FilenameCompleter,
+}
+
+impl Completer for CommandHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map_or(0, |space| space + 1);
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+        let candidates: &[&str] = match words.as_slice() {
+            [] => &COMMANDS,
+            ["move"] if self.rules.diagonal_moves => &DIRECTIONS,
+            ["move"] => &DIRECTIONS[..4],
+            ["save" | "load"] => return self.filenames.complete_path(line, pos),
+            _ => &[],
+        };
+        let pairs = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(&line[start..]))
+            .map(|candidate| Pair {
+                display: candidate.to_string(),
+                replacement: candidate.to_string(),
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+fn get_history_path() -> Option<String> {
+    std::env::var("HOME")
+        .ok()
+        .map(|home| format!("{}/{}", home, HISTORY_FILE))
+}
+
+pub fn run(mut session: Session) -> rustyline::Result<()> {
+    let mut editor = Editor::new()?;
+    editor.set_helper(Some(CommandHelper {
+        rules: session.grid.get_rules(),
+        filenames: FilenameCompleter::new(),
+    }));
+    let history = get_history_path();
+    if let Some(path) = &history {
+        // There is no history before the first session
+        let _ = editor.load_history(path);
+    }
+    println!("{}\ntype help for the commands", session.show());
+    loop {
+        let line = match editor.readline("2048> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error),
+        };
+        editor.add_history_entry(line.as_str())?;
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        match session.execute(&line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(error) => println!("error: {}", error),
+        }
+    }
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cell, parse_tile, Session};
+    use game2048::grid::{Grid, Rules};
+    use game2048::heuristic::WeightedHeuristic;
+
+    fn session(notation: &str) -> Session {
+        Session::new(notation.parse().unwrap(), WeightedHeuristic::default())
+    }
+
+    #[test]
+    fn edits_and_undo() {
+        let mut session = session("0,0,0,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        session.execute("set 0 0 2").unwrap();
+        session.execute("spawn 0 3 2").unwrap();
+        assert_eq!(session.grid.to_string(), "2,0,0,2/0,0,0,0/0,0,0,0/0,0,0,0");
+        assert!(session.execute("spawn 0 3 4").is_err());
+
+        session.execute("move left").unwrap();
+        assert_eq!(session.grid.to_string(), "4,0,0,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        assert_eq!(session.grid.get_score(), 4);
+        assert!(session.execute("move left").is_err());
+
+        session.execute("undo").unwrap();
+        assert_eq!(session.grid.to_string(), "2,0,0,2/0,0,0,0/0,0,0,0/0,0,0,0");
+        session.execute("undo").unwrap();
+        session.execute("undo").unwrap();
+        assert_eq!(session.grid.to_string(), "0,0,0,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        assert_eq!(session.execute("undo"), Err("nothing to undo".to_string()));
+    }
+
+    #[test]
+    fn moves_score_like_the_game() {
+        let mut session = session("2,2,2,4/2,4,4,4/0,0,0,0/0,0,0,0");
+        session.execute("move right").unwrap();
+        assert_eq!(session.grid.to_string(), "0,2,4,4/0,2,4,8/0,0,0,0/0,0,0,0");
+        assert_eq!(session.grid.get_score(), 12);
+    }
+
+    #[test]
+    fn positions_keep_the_rules() {
+        let wrap: Rules = "wrap".parse().unwrap();
+        let empty = Grid::new_with_rules([[0; 4]; 4], wrap);
+        let mut session = Session::new(empty, WeightedHeuristic::default());
+        session
+            .execute("position 2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2")
+            .unwrap();
+        assert_eq!(session.grid.to_string(), "2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2");
+        assert_eq!(session.grid.get_rules(), wrap);
+        assert!(session.execute("position 2,0,0").is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("game2048_repl_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut session = session("2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2");
+        session.execute(&format!("save {}", path)).unwrap();
+        session.execute("set 1 1 0").unwrap();
+        session.execute(&format!("load {}", path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(session.grid.to_string(), "2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2");
+        assert!(session.execute(&format!("load {}", path)).is_err());
+    }
+
+    #[test]
+    fn rejected_commands() {
+        let mut session = session("2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2");
+        assert_eq!(
+            session.execute("set 4 0 2"),
+            Err("invalid row or column '4', expected 0 to 3".to_string())
+        );
+        assert_eq!(
+            session.execute("set 0 0 3"),
+            Err("invalid tile '3'".to_string())
+        );
+        assert_eq!(
+            session.execute("spawn 2 2 8"),
+            Err("only 2 and 4 spawn, not '8'".to_string())
+        );
+        assert!(session.execute("move sideways").is_err());
+        assert!(session.execute("hint deep").is_err());
+        assert_eq!(
+            session.execute("set 0 0"),
+            Err("wrong arguments for set, see help".to_string())
+        );
+        assert_eq!(
+            session.execute("jump"),
+            Err("unknown command 'jump', see help".to_string())
+        );
+        // Nothing was changed
+        assert_eq!(session.execute("undo"), Err("nothing to undo".to_string()));
+    }
+
+    #[test]
+    fn cells_and_tiles() {
+        assert_eq!(parse_cell("3", "0"), Ok((3, 0)));
+        assert!(parse_cell("0", "4").is_err());
+        assert!(parse_cell("-1", "0").is_err());
+        assert!(parse_cell("a", "0").is_err());
+
+        assert_eq!(parse_tile("0"), Ok(0));
+        assert_eq!(parse_tile("2"), Ok(2));
+        assert_eq!(parse_tile("65536"), Ok(65536));
+        assert!(parse_tile("1").is_err());
+        assert!(parse_tile("6").is_err());
+        assert!(parse_tile("two").is_err());
+    }
+}
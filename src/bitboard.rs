@@ -0,0 +1,332 @@
+use std::sync::OnceLock;
+
+use rand::distributions::{Bernoulli, Distribution};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::grid::{slide_line, Grid, Move, CHANCE_FOR_TWO, MOVES};
+
+// Largest exponent a cell can hold, i.e. tiles up to 32768
+const MAX_EXPONENT: u8 = 15;
+// Marks the rows of the move tables whose move would create a tile above 32768
+const OVERFLOW: u32 = u32::MAX;
+
+// Packed 4x4 board for fast simulation of the classic rules. Every cell takes 4 bits
+// holding log2 of its tile, 0 for empty. Cell (x, y) lives in bits 4 * (4x + y), so
+// every row is one 16 bit block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+struct MoveTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    left_score: Vec<u32>,
+    right_score: Vec<u32>,
+}
+
+// Results of moving every possible row left or right, computed once with the same slide
+// and merge logic as `Grid`
+fn tables() -> &'static MoveTables {
+    static TABLES: OnceLock<MoveTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut tables = MoveTables {
+            left: vec![0; 1 << 16],
+            right: vec![0; 1 << 16],
+            left_score: vec![0; 1 << 16],
+            right_score: vec![0; 1 << 16],
+        };
+        for row in 0..=u16::MAX {
+            let values = [0, 1, 2, 3].map(|y| exponent_to_value(((row >> (4 * y)) & 0xF) as u8));
+
+            let mut right = values;
+            let right_score: u64 = slide_line(&mut right).iter().sum();
+            let mut left = values;
+            left.reverse();
+            let left_score: u64 = slide_line(&mut left).iter().sum();
+            left.reverse();
+
+            let row = row as usize;
+            (tables.right[row], tables.right_score[row]) = pack_moved_row(right, right_score);
+            (tables.left[row], tables.left_score[row]) = pack_moved_row(left, left_score);
+        }
+        tables
+    })
+}
+
+fn exponent_to_value(exponent: u8) -> u64 {
+    if exponent == 0 {
+        0
+    } else {
+        1 << exponent
+    }
+}
+
+fn fits(value: u64) -> bool {
+    value <= 1 << MAX_EXPONENT
+}
+
+fn value_to_exponent(value: u64) -> u8 {
+    assert!(fits(value), "tile {} doesn't fit in a bitboard", value);
+    if value == 0 {
+        0
+    } else {
+        value.trailing_zeros() as u8
+    }
+}
+
+fn pack_row(values: [u64; 4]) -> u16 {
+    values.iter().enumerate().fold(0, |row, (y, value)| {
+        row | (value_to_exponent(*value) as u16) << (4 * y)
+    })
+}
+
+fn pack_moved_row(values: [u64; 4], score: u64) -> (u16, u32) {
+    if values.iter().all(|value| fits(*value)) {
+        (pack_row(values), score as u32)
+    } else {
+        (0, OVERFLOW)
+    }
+}
+
+impl Bitboard {
+    // Panics if a tile is above 32768
+    pub fn from_cells(cells: &[[u64; 4]; 4]) -> Self {
+        let mut board = 0;
+        for (x, row) in cells.iter().enumerate() {
+            board |= (pack_row(*row) as u64) << (16 * x);
+        }
+        Self(board)
+    }
+
+    pub fn from_grid(grid: &Grid) -> Self {
+        Self::from_cells(&grid.cells)
+    }
+
+    // None if a tile is above 32768
+    pub fn try_from_grid(grid: &Grid) -> Option<Self> {
+        if grid.cells.iter().flatten().all(|cell| fits(*cell)) {
+            Some(Self::from_grid(grid))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_cells(self) -> [[u64; 4]; 4] {
+        let mut cells = [[0; 4]; 4];
+        for (x, row) in cells.iter_mut().enumerate() {
+            for (y, cell) in row.iter_mut().enumerate() {
+                *cell = exponent_to_value(self.get_exponent(x, y));
+            }
+        }
+        cells
+    }
+
+    pub fn get_exponent(self, x: usize, y: usize) -> u8 {
+        ((self.0 >> (4 * (4 * x + y))) & 0xF) as u8
+    }
+
+    pub fn count_empty(self) -> usize {
+        (0..16).filter(|i| (self.0 >> (4 * i)) & 0xF == 0).count()
+    }
+
+    pub fn max_exponent(self) -> u8 {
+        (0..16)
+            .map(|i| ((self.0 >> (4 * i)) & 0xF) as u8)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // The afterstate of a move under the classic rules and the score it gains, or None if
+    // the move doesn't change anything. Diagonal moves aren't supported, and neither are
+    // moves merging two 32768 tiles, since 65536 doesn't fit in a cell.
+    pub fn after_move(self, mov: Move) -> Option<(Bitboard, u32)> {
+        let tables = tables();
+        let (board, score) = match mov {
+            Move::Left => Self::move_rows(self.0, &tables.left, &tables.left_score)?,
+            Move::Right => Self::move_rows(self.0, &tables.right, &tables.right_score)?,
+            Move::Up => {
+                let (board, score) =
+                    Self::move_rows(transpose(self.0), &tables.left, &tables.left_score)?;
+                (transpose(board), score)
+            }
+            Move::Down => {
+                let (board, score) =
+                    Self::move_rows(transpose(self.0), &tables.right, &tables.right_score)?;
+                (transpose(board), score)
+            }
+            _ => return None,
+        };
+        if board == self.0 {
+            return None;
+        }
+        Some((Bitboard(board), score))
+    }
+
+    fn move_rows(board: u64, rows: &[u16], scores: &[u32]) -> Option<(u64, u32)> {
+        let mut moved = 0;
+        let mut score = 0;
+        for x in 0..4 {
+            let row = ((board >> (16 * x)) & 0xFFFF) as usize;
+            if scores[row] == OVERFLOW {
+                return None;
+            }
+            moved |= (rows[row] as u64) << (16 * x);
+            score += scores[row];
+        }
+        Some((moved, score))
+    }
+
+    pub fn is_lost(self) -> bool {
+        MOVES.iter().all(|mov| self.after_move(*mov).is_none())
+    }
+
+    // Spawns a 2 or a 4 in a random empty cell. The random numbers are drawn in the same
+    // order as `Grid` draws them, so the same seed spawns the same tiles on both.
+    pub fn spawn<R: Rng>(self, rng: &mut R) -> Bitboard {
+        let mut empty = [0; 16];
+        let mut count = 0;
+        for i in 0..16 {
+            if (self.0 >> (4 * i)) & 0xF == 0 {
+                empty[count] = i;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return self;
+        }
+        let exponent: u64 = if Bernoulli::new(CHANCE_FOR_TWO).unwrap().sample(rng) {
+            1
+        } else {
+            2
+        };
+        // Checked above that there is an empty cell
+        let i = empty[..count].choose(rng).unwrap();
+        Bitboard(self.0 | exponent << (4 * i))
+    }
+}
+
+fn transpose(x: u64) -> u64 {
+    let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+    let a2 = x & 0x0000_F0F0_0000_F0F0;
+    let a3 = x & 0x0F0F_0000_0F0F_0000;
+    let a = a1 | (a2 << 12) | (a3 >> 12);
+    let b1 = a & 0xFF00_FF00_00FF_00FF;
+    let b2 = a & 0x00FF_00FF_0000_0000;
+    let b3 = a & 0x0000_0000_FF00_FF00;
+    b1 | (b2 >> 24) | (b3 << 24)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transpose, Bitboard};
+    use crate::grid::{Grid, Move, MOVES};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn cells() -> [[u64; 4]; 4] {
+        let row1 = [2, 2, 4, 8];
+        let row2 = [0, 4, 0, 4];
+        let row3 = [16, 0, 16, 2];
+        let row4 = [2, 0, 0, 32768];
+        [row1, row2, row3, row4]
+    }
+
+    #[test]
+    fn packing_round_trips() {
+        let board = Bitboard::from_cells(&cells());
+        assert_eq!(board.to_cells(), cells());
+        assert_eq!(board.get_exponent(3, 3), 15);
+        assert_eq!(board.count_empty(), 5);
+        assert_eq!(board.max_exponent(), 15);
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let board = Bitboard::from_cells(&cells());
+        let transposed = Bitboard(transpose(board.0)).to_cells();
+        for (x, row) in transposed.iter().enumerate() {
+            for (y, cell) in row.iter().enumerate() {
+                assert_eq!(*cell, cells()[y][x]);
+            }
+        }
+    }
+
+    #[test]
+    fn moves_match_the_grid() {
+        let grid = Grid::new(cells());
+        let board = Bitboard::from_grid(&grid);
+        for mov in MOVES {
+            let (after, score) = board.after_move(mov).unwrap();
+            let expected = grid.after_move(mov).unwrap();
+            assert_eq!(after.to_cells(), expected.cells);
+            assert_eq!(score as u64, expected.get_score());
+        }
+    }
+
+    #[test]
+    fn invalid_moves_are_none() {
+        let row1 = [2, 4, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let board = Bitboard::from_cells(&[row1, row2, row3, row4]);
+        assert!(board.after_move(Move::Left).is_none());
+        assert!(board.after_move(Move::Up).is_none());
+        assert!(board.after_move(Move::UpLeft).is_none());
+        assert!(board.after_move(Move::Right).is_some());
+    }
+
+    #[test]
+    fn spawn_fills_one_empty_cell() {
+        let board = Bitboard::from_cells(&cells());
+        let mut rng = StdRng::seed_from_u64(4);
+        let spawned = board.spawn(&mut rng);
+        assert_eq!(spawned.count_empty(), board.count_empty() - 1);
+        assert_eq!(spawned.0 & board.0, board.0);
+    }
+
+    #[test]
+    fn seeded_games_match_grid() {
+        let mut grid = Grid::new([[2, 0, 0, 0], [0; 4], [0, 0, 4, 0], [0; 4]]);
+        let mut board = Bitboard::from_cells(&grid.cells);
+        let mut grid_rng = StdRng::seed_from_u64(12);
+        let mut board_rng = StdRng::seed_from_u64(12);
+        for mov in MOVES.iter().cycle().take(200) {
+            if let Some((moved, _)) = board.after_move(*mov) {
+                board = moved.spawn(&mut board_rng);
+                grid.attempt_with_rng(*mov, &mut grid_rng);
+            }
+            assert_eq!(board.to_cells(), grid.cells);
+        }
+    }
+
+    #[test]
+    fn tiles_above_32768_are_rejected() {
+        let row1 = [32768, 32768, 0, 0];
+        let row2 = [0, 0, 0, 2];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let board = Bitboard::from_cells(&[row1, row2, row3, row4]);
+        assert!(board.after_move(Move::Left).is_none());
+        assert!(board.after_move(Move::Right).is_none());
+        assert!(board.after_move(Move::Down).is_some());
+
+        let grid = Grid::new([[65536, 0, 0, 0], row2, row3, row4]);
+        assert!(Bitboard::try_from_grid(&grid).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit")]
+    fn packing_65536_panics() {
+        Bitboard::from_cells(&[[65536, 0, 0, 0], [0; 4], [0; 4], [0; 4]]);
+    }
+
+    #[test]
+    fn lost_board_is_lost() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [2, 4, 2, 4];
+        let row4 = [4, 2, 4, 2];
+        assert!(Bitboard::from_cells(&[row1, row2, row3, row4]).is_lost());
+        assert!(!Bitboard::from_cells(&cells()).is_lost());
+    }
+}
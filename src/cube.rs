@@ -0,0 +1,201 @@
+use rand::{distributions::Bernoulli, prelude::Distribution, seq::SliceRandom};
+
+use crate::grid::{slide_line, GameStatus, CHANCE_FOR_TWO};
+
+pub const CUBE_MOVES: [CubeMove; 6] = [
+    CubeMove::Left,
+    CubeMove::Right,
+    CubeMove::Up,
+    CubeMove::Down,
+    CubeMove::In,
+    CubeMove::Out,
+];
+
+// In moves tiles towards the last layer, Out towards the first one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CubeMove {
+    Left,
+    Right,
+    Up,
+    Down,
+    In,
+    Out,
+}
+
+// 4x4x4 cube, indexed as cells[layer][row][column]
+#[derive(PartialEq, Debug)]
+pub struct Cube {
+    pub cells: [[[u64; 4]; 4]; 4],
+    score: u64,
+}
+
+impl Cube {
+    fn new(cells: [[[u64; 4]; 4]; 4]) -> Self {
+        Self { cells, score: 0 }
+    }
+
+    pub fn new_random() -> Self {
+        let mut cube = Self::new([[[0; 4]; 4]; 4]);
+        cube.insert_random_cell();
+        cube.insert_random_cell();
+        cube
+    }
+
+    pub fn get_score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn attempt(&mut self, mov: CubeMove) -> GameStatus {
+        let (new_cells, score_increase) = Self::make_move(self.cells, mov);
+        if new_cells == self.cells {
+            return GameStatus::InvalidMove;
+        }
+        self.cells = new_cells;
+        self.score += score_increase;
+
+        self.insert_random_cell();
+        if self.has_player_lost() {
+            return GameStatus::Lost;
+        }
+        GameStatus::Ok
+    }
+
+    pub fn has_player_lost(&self) -> bool {
+        CUBE_MOVES
+            .iter()
+            .all(|mov| Self::make_move(self.cells, *mov).0 == self.cells)
+    }
+
+    fn insert_random_cell(&mut self) {
+        let mut empty_cells = Vec::new();
+        for (z, layer) in self.cells.iter().enumerate() {
+            for (y, row) in layer.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    if *cell == 0 {
+                        empty_cells.push((z, y, x));
+                    }
+                }
+            }
+        }
+        let mut rng = rand::thread_rng();
+        if let Some((z, y, x)) = empty_cells.choose(&mut rng) {
+            let bern = Bernoulli::new(CHANCE_FOR_TWO).unwrap();
+            self.cells[*z][*y][*x] = if bern.sample(&mut rng) { 2 } else { 4 };
+        }
+    }
+
+    // The 16 lines running along the axis of the move, each ordered so that its end
+    // lies on the face the tiles move towards.
+    fn lines(mov: CubeMove) -> Vec<[(usize, usize, usize); 4]> {
+        let mut lines = Vec::new();
+        for a in 0..4 {
+            for b in 0..4 {
+                let line = [0, 1, 2, 3].map(|i| match mov {
+                    CubeMove::Right => (a, b, i),
+                    CubeMove::Left => (a, b, 3 - i),
+                    CubeMove::Down => (a, i, b),
+                    CubeMove::Up => (a, 3 - i, b),
+                    CubeMove::In => (i, a, b),
+                    CubeMove::Out => (3 - i, a, b),
+                });
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    fn make_move(mut cells: [[[u64; 4]; 4]; 4], mov: CubeMove) -> ([[[u64; 4]; 4]; 4], u64) {
+        let mut score_increase = 0;
+        for line in Self::lines(mov) {
+            let mut values = line.map(|(z, y, x)| cells[z][y][x]);
+            score_increase += slide_line(&mut values).iter().sum::<u64>();
+            for ((z, y, x), value) in line.into_iter().zip(values) {
+                cells[z][y][x] = value;
+            }
+        }
+        (cells, score_increase)
+    }
+}
+
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new_random()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cube, CubeMove};
+    use crate::grid::GameStatus;
+
+    #[test]
+    fn move_in_merges_along_the_depth_axis() {
+        let mut cells = [[[0; 4]; 4]; 4];
+        cells[0][1][2] = 2;
+        cells[1][1][2] = 2;
+        cells[3][1][2] = 4;
+
+        let (cells, score) = Cube::make_move(cells, CubeMove::In);
+
+        let mut expected = [[[0; 4]; 4]; 4];
+        expected[2][1][2] = 4;
+        expected[3][1][2] = 4;
+        assert_eq!(cells, expected);
+        assert_eq!(score, 4);
+    }
+
+    #[test]
+    fn move_out_merges_each_tile_once() {
+        let mut cells = [[[0; 4]; 4]; 4];
+        for layer in cells.iter_mut() {
+            layer[0][0] = 2;
+        }
+
+        let (cells, score) = Cube::make_move(cells, CubeMove::Out);
+
+        assert_eq!(cells[0][0][0], 4);
+        assert_eq!(cells[1][0][0], 4);
+        assert_eq!(cells[2][0][0], 0);
+        assert_eq!(cells[3][0][0], 0);
+        assert_eq!(score, 8);
+    }
+
+    #[test]
+    fn planar_moves_work_inside_every_layer() {
+        let mut cells = [[[0; 4]; 4]; 4];
+        cells[2][3] = [2, 2, 4, 0];
+
+        let (right, _) = Cube::make_move(cells, CubeMove::Right);
+        assert_eq!(right[2][3], [0, 0, 4, 4]);
+
+        let (up, _) = Cube::make_move(cells, CubeMove::Up);
+        assert_eq!(up[2][0], [2, 2, 4, 0]);
+        assert_eq!(up[2][3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn move_that_changes_nothing_is_invalid() {
+        let mut cells = [[[0; 4]; 4]; 4];
+        cells[3][0][0] = 2;
+        let mut cube = Cube::new(cells);
+
+        assert_eq!(cube.attempt(CubeMove::In), GameStatus::InvalidMove);
+    }
+
+    #[test]
+    fn player_has_lost_when_no_neighbours_are_equal() {
+        let mut cells = [[[0; 4]; 4]; 4];
+        for (z, layer) in cells.iter_mut().enumerate() {
+            for (y, row) in layer.iter_mut().enumerate() {
+                for (x, cell) in row.iter_mut().enumerate() {
+                    *cell = if (x + y + z) % 2 == 0 { 2 } else { 4 };
+                }
+            }
+        }
+        let mut cube = Cube::new(cells);
+        assert!(cube.has_player_lost());
+
+        cube.cells[1][2][3] = 0;
+        assert!(!cube.has_player_lost());
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::grid::{GameStatus, Grid, Move, Rules, DIAGONAL_MOVES, MOVES};
+
+// Tiles above 2^(planes - 1) share the last one-hot plane
+const DEFAULT_PLANES: usize = 18;
+
+#[derive(Debug, PartialEq)]
+pub enum EnvError {
+    // The action index is out of range
+    UnknownAction(usize),
+    // The move can't be played and invalid moves are masked
+    InvalidAction(Move),
+    // `step` was called after the game ended, without a `reset`
+    GameOver,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::UnknownAction(action) => write!(f, "unknown action {}", action),
+            EnvError::InvalidAction(mov) => write!(f, "move {} isn't valid", mov),
+            EnvError::GameOver => write!(f, "the game is over, reset the environment"),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+// How the grid is turned into an observation. Cells are read row by row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    // The tile values, 0 for empty cells
+    Raw,
+    // log2 of the tile values, 0 for empty cells
+    Log2,
+    // One plane of 16 cells per exponent, 1 where the cell has that exponent. Plane 0 is
+    // the empty cells. There is always at least one plane, 0 planes count as 1.
+    OneHot { planes: usize },
+}
+
+impl Encoding {
+    pub fn get_size(&self) -> usize {
+        match self {
+            Encoding::Raw | Encoding::Log2 => 16,
+            Encoding::OneHot { planes } => 16 * (*planes).max(1),
+        }
+    }
+
+    pub fn encode(&self, grid: &Grid) -> Vec<f32> {
+        let cells = grid.cells.iter().flatten();
+        match self {
+            Encoding::Raw => cells.map(|cell| *cell as f32).collect(),
+            Encoding::Log2 => cells.map(|cell| get_exponent(*cell) as f32).collect(),
+            Encoding::OneHot { planes } => {
+                let planes = (*planes).max(1);
+                let mut observation = vec![0.0; self.get_size()];
+                for (i, cell) in cells.enumerate() {
+                    let plane = get_exponent(*cell).min(planes - 1);
+                    observation[plane * 16 + i] = 1.0;
+                }
+                observation
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reward {
+    // Points scored by the move
+    ScoreGain,
+    // Number of merges made by the move
+    MergeCount,
+    // 1 for every move played
+    Survival,
+}
+
+// What happens when the action is a move that can't be played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidMove {
+    // Nothing moves and the reward is minus the penalty
+    Penalty(f32),
+    // Nothing moves and the reward is 0
+    NoOp,
+    // The agent is expected to respect the action mask, `step` returns an error
+    Masked,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Info {
+    pub score: u64,
+    pub max_tile: u64,
+    pub moves: usize,
+    // The action couldn't be played
+    pub invalid_move: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    pub done: bool,
+    pub info: Info,
+}
+
+// Reinforcement learning environment around `Grid`, in the style of Gym. Actions are
+// indices into `get_actions`: the four moves, followed by the diagonal moves when the
+// rules allow them.
+pub struct Env {
+    rules: Rules,
+    encoding: Encoding,
+    reward: Reward,
+    invalid_move: InvalidMove,
+    grid: Grid,
+    moves: usize,
+    rng: StdRng,
+}
+
+impl Env {
+    pub fn new(rules: Rules) -> Self {
+        let mut rng = StdRng::seed_from_u64(0);
+        Self {
+            rules,
+            encoding: Encoding::Log2,
+            reward: Reward::ScoreGain,
+            invalid_move: InvalidMove::NoOp,
+            grid: Grid::new_random_with_rng(rules, &mut rng),
+            moves: 0,
+            rng,
+        }
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_one_hot_encoding(self) -> Self {
+        self.with_encoding(Encoding::OneHot {
+            planes: DEFAULT_PLANES,
+        })
+    }
+
+    pub fn with_reward(mut self, reward: Reward) -> Self {
+        self.reward = reward;
+        self
+    }
+
+    pub fn with_invalid_move(mut self, invalid_move: InvalidMove) -> Self {
+        self.invalid_move = invalid_move;
+        self
+    }
+
+    pub fn get_grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn get_actions(&self) -> Vec<Move> {
+        let mut actions = MOVES.to_vec();
+        if self.rules.diagonal_moves {
+            actions.extend(DIAGONAL_MOVES);
+        }
+        actions
+    }
+
+    pub fn get_observation_size(&self) -> usize {
+        self.encoding.get_size()
+    }
+
+    // Starts a new game. The same seed always gives the same game for the same actions.
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.grid = Grid::new_random_with_rng(self.rules, &mut self.rng);
+        self.moves = 0;
+        self.observation()
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        self.encoding.encode(&self.grid)
+    }
+
+    // Whether every action can be played
+    pub fn action_mask(&self) -> Vec<bool> {
+        let valid = self.grid.get_valid_moves();
+        self.get_actions()
+            .iter()
+            .map(|mov| valid.contains(mov))
+            .collect()
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Step, EnvError> {
+        let mov = *self
+            .get_actions()
+            .get(action)
+            .ok_or(EnvError::UnknownAction(action))?;
+        if self.grid.has_player_lost() {
+            return Err(EnvError::GameOver);
+        }
+        let score = self.grid.get_score();
+        let (reward, invalid_move) = match self.grid.attempt_with_rng(mov, &mut self.rng) {
+            GameStatus::InvalidMove => match self.invalid_move {
+                InvalidMove::Penalty(penalty) => (-penalty, true),
+                InvalidMove::NoOp => (0.0, true),
+                InvalidMove::Masked => return Err(EnvError::InvalidAction(mov)),
+            },
+            _ => {
+                self.moves += 1;
+                let reward = match self.reward {
+                    Reward::ScoreGain => (self.grid.get_score() - score) as f32,
+                    Reward::MergeCount => self.grid.get_last_merges().len() as f32,
+                    Reward::Survival => 1.0,
+                };
+                (reward, false)
+            }
+        };
+        Ok(Step {
+            observation: self.observation(),
+            reward,
+            done: self.grid.has_player_lost(),
+            info: Info {
+                score: self.grid.get_score(),
+                max_tile: self.grid.cells.iter().flatten().copied().max().unwrap_or(0),
+                moves: self.moves,
+                invalid_move,
+            },
+        })
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new(Rules::default())
+    }
+}
+
+fn get_exponent(value: u64) -> usize {
+    if value == 0 {
+        0
+    } else {
+        value.trailing_zeros() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoding, Env, EnvError, InvalidMove, Reward};
+    use crate::grid::{Grid, Move, Rules};
+
+    #[test]
+    fn same_seed_same_episode() {
+        let mut first = Env::default();
+        let mut second = Env::default();
+        assert_eq!(first.reset(7), second.reset(7));
+        for action in [0, 3, 1, 2, 3, 3, 0] {
+            assert_eq!(first.step(action), second.step(action));
+        }
+    }
+
+    #[test]
+    fn encodings() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 1024, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 4];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let raw = Encoding::Raw.encode(&grid);
+        assert_eq!((raw[0], raw[5], raw[15]), (2.0, 1024.0, 4.0));
+        let log2 = Encoding::Log2.encode(&grid);
+        assert_eq!((log2[0], log2[1], log2[5], log2[15]), (1.0, 0.0, 10.0, 2.0));
+
+        let one_hot = Encoding::OneHot { planes: 4 };
+        let planes = one_hot.encode(&grid);
+        assert_eq!(planes.len(), 64);
+        assert_eq!(planes.iter().sum::<f32>(), 16.0);
+        assert_eq!(planes[16], 1.0);
+        // 1024 shares the last plane
+        assert_eq!(planes[3 * 16 + 5], 1.0);
+
+        let one_plane = Encoding::OneHot { planes: 0 }.encode(&grid);
+        assert_eq!(one_plane, vec![1.0; 16]);
+    }
+
+    #[test]
+    fn invalid_moves() {
+        // Find a start with a move that can't be played
+        let mut env = Env::default().with_invalid_move(InvalidMove::Penalty(5.0));
+        let (seed, invalid) = (0..)
+            .find_map(|seed| {
+                env.reset(seed);
+                let mask = env.action_mask();
+                (0..4)
+                    .find(|action| !mask[*action])
+                    .map(|action| (seed, action))
+            })
+            .unwrap();
+        let step = env.step(invalid).unwrap();
+        assert_eq!(step.reward, -5.0);
+        assert!(step.info.invalid_move);
+        assert_eq!(step.info.moves, 0);
+
+        let mut env = Env::default().with_invalid_move(InvalidMove::Masked);
+        env.reset(seed);
+        assert!(matches!(env.step(invalid), Err(EnvError::InvalidAction(_))));
+        assert_eq!(env.step(4), Err(EnvError::UnknownAction(4)));
+    }
+
+    #[test]
+    fn rewards() {
+        let rules = Rules {
+            diagonal_moves: true,
+            ..Rules::default()
+        };
+        let mut env = Env::new(rules).with_reward(Reward::Survival);
+        assert_eq!(env.get_actions().len(), 8);
+        env.reset(3);
+        let action = env.action_mask().iter().position(|valid| *valid).unwrap();
+        assert_eq!(env.step(action).unwrap().reward, 1.0);
+        assert!(env.get_actions().contains(&Move::DownRight));
+    }
+}
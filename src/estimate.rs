@@ -0,0 +1,182 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::grid::Grid;
+use crate::monte_carlo::RolloutPolicy;
+
+const DEFAULT_ROLLOUTS: usize = 200;
+const DEFAULT_HORIZON: usize = 20;
+// Rollouts that haven't reached the target after this many moves count as failures
+const DEFAULT_MAX_MOVES: usize = 2000;
+
+// Outcome of a number of rollouts from one position. The probabilities are those of a
+// player following the rollout policy, not of a perfect player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    // The next power of two above the biggest tile
+    pub target: u64,
+    pub horizon: usize,
+    pub rollouts: usize,
+    // Rollouts that reached the target
+    pub reached: usize,
+    // Rollouts that lost within the horizon
+    pub died: usize,
+}
+
+impl Estimate {
+    pub fn new(grid: &Grid, horizon: usize) -> Self {
+        let max_tile = grid.cells.iter().flatten().copied().max().unwrap_or(0);
+        Self {
+            target: (max_tile * 2).max(2),
+            horizon,
+            rollouts: 0,
+            reached: 0,
+            died: 0,
+        }
+    }
+
+    pub fn reach_probability(&self) -> f64 {
+        self.reached as f64 / self.rollouts.max(1) as f64
+    }
+
+    pub fn death_probability(&self) -> f64 {
+        self.died as f64 / self.rollouts.max(1) as f64
+    }
+
+    // Adds the rollouts of another estimate of the same position
+    pub fn merge(&mut self, other: &Estimate) {
+        self.rollouts += other.rollouts;
+        self.reached += other.reached;
+        self.died += other.died;
+    }
+}
+
+// Estimates the chance of reaching the next tile and of losing within a number of moves
+// by playing the position out many times
+pub struct Estimator {
+    rollouts: usize,
+    horizon: usize,
+    max_moves: usize,
+    policy: RolloutPolicy,
+    rng: StdRng,
+}
+
+impl Estimator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rollouts: DEFAULT_ROLLOUTS,
+            horizon: DEFAULT_HORIZON,
+            max_moves: DEFAULT_MAX_MOVES,
+            policy: RolloutPolicy::Greedy,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn with_rollouts(mut self, rollouts: usize) -> Self {
+        self.rollouts = rollouts.max(1);
+        self
+    }
+
+    pub fn with_horizon(mut self, horizon: usize) -> Self {
+        self.horizon = horizon;
+        self
+    }
+
+    pub fn with_max_moves(mut self, moves: usize) -> Self {
+        self.max_moves = moves;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RolloutPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn get_horizon(&self) -> usize {
+        self.horizon
+    }
+
+    pub fn estimate(&mut self, grid: &Grid) -> Estimate {
+        let mut estimate = Estimate::new(grid, self.horizon);
+        for _ in 0..self.rollouts {
+            let (reached, died) = self.rollout(grid, estimate.target);
+            estimate.rollouts += 1;
+            estimate.reached += reached as usize;
+            estimate.died += died as usize;
+        }
+        estimate
+    }
+
+    // Plays until the target is reached and the horizon is passed, or the game is lost.
+    // Returns whether the target was reached and whether the game was lost in the horizon.
+    fn rollout(&mut self, grid: &Grid, target: u64) -> (bool, bool) {
+        let mut grid = grid.clone();
+        let mut reached = false;
+        for moves in 0..self.max_moves {
+            if grid.has_player_lost() {
+                return (reached, moves <= self.horizon);
+            }
+            if reached && moves >= self.horizon {
+                break;
+            }
+            let mov = match self.policy.choose(&grid, &mut self.rng) {
+                Some(mov) => mov,
+                None => return (reached, moves <= self.horizon),
+            };
+            grid.attempt_with_rng(mov, &mut self.rng);
+            reached |= grid.cells.iter().flatten().any(|cell| *cell >= target);
+        }
+        (reached, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Estimate, Estimator};
+    use crate::grid::Grid;
+
+    #[test]
+    fn lost_games_stay_lost() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [2, 4, 2, 4];
+        let row4 = [4, 2, 4, 8];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let estimate = Estimator::new(1).with_rollouts(10).estimate(&grid);
+        assert_eq!(estimate.target, 16);
+        assert_eq!(estimate.rollouts, 10);
+        assert_eq!(estimate.reach_probability(), 0.0);
+        assert_eq!(estimate.death_probability(), 1.0);
+    }
+
+    #[test]
+    fn merges_within_reach_are_found() {
+        // Moving left or right merges the two 1024s straight away
+        let row1 = [1024, 1024, 0, 0];
+        let row2 = [2, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let estimate = Estimator::new(2).with_rollouts(20).estimate(&grid);
+        assert_eq!(estimate.target, 2048);
+        assert_eq!(estimate.reach_probability(), 1.0);
+        assert_eq!(estimate.death_probability(), 0.0);
+    }
+
+    #[test]
+    fn estimates_add_up() {
+        let grid = Grid::new_random();
+        let mut estimator = Estimator::new(3).with_rollouts(5).with_horizon(10);
+        let mut total = Estimate::new(&grid, estimator.get_horizon());
+        for _ in 0..3 {
+            total.merge(&estimator.estimate(&grid));
+        }
+        assert_eq!(total.rollouts, 15);
+        assert!(total.reached <= 15 && total.died <= 15);
+        assert_eq!(
+            Estimator::new(3).with_rollouts(5).estimate(&grid),
+            Estimator::new(3).with_rollouts(5).estimate(&grid)
+        );
+    }
+}
@@ -1,13 +1,87 @@
-use rand::{seq::SliceRandom, distributions::Bernoulli, prelude::Distribution};
+use std::fmt;
+use std::str::FromStr;
 
-const MOVES: [Move; 4] = [Move::Left, Move::Right, Move::Up, Move::Down];
-const CHANCE_FOR_TWO: f64 = 0.9;
+use rand::{seq::SliceRandom, distributions::Bernoulli, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Debug)]
+pub const MOVES: [Move; 4] = [Move::Left, Move::Right, Move::Up, Move::Down];
+pub const DIAGONAL_MOVES: [Move; 4] = [Move::UpLeft, Move::UpRight, Move::DownLeft, Move::DownRight];
+pub const CHANCE_FOR_TWO: f64 = 0.9;
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Grid {
     // 4x4 grid
     pub cells: [[u64; 4]; 4],
-    score: u64
+    score: u64,
+    // Values of the tiles created by merges during the last successful attempt
+    last_merges: Vec<u64>,
+    // Position and value of the tile spawned by the last successful attempt
+    last_spawn: Option<(usize, usize, u64)>,
+    rules: Rules,
+}
+
+// Optional rule variants. The default is the classic game. Serialized by name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Rules {
+    pub topology: Topology,
+    // Allows the four diagonal moves on top of Left, Right, Up and Down
+    pub diagonal_moves: bool,
+}
+
+// Names of the rule variants, as used by `Rules::from_str`
+pub const RULE_NAMES: [&str; 4] = ["classic", "wrap", "diagonal", "wrap-diagonal"];
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match (self.topology, self.diagonal_moves) {
+            (Topology::Flat, false) => "classic",
+            (Topology::Torus, false) => "wrap",
+            (Topology::Flat, true) => "diagonal",
+            (Topology::Torus, true) => "wrap-diagonal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl From<Rules> for String {
+    fn from(rules: Rules) -> Self {
+        rules.to_string()
+    }
+}
+
+impl TryFrom<String> for Rules {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let (topology, diagonal_moves) = match name {
+            "classic" => (Topology::Flat, false),
+            "wrap" => (Topology::Torus, false),
+            "diagonal" => (Topology::Flat, true),
+            "wrap-diagonal" => (Topology::Torus, true),
+            _ => return Err(format!("unknown rules '{}', expected one of {}", name, RULE_NAMES.join(", "))),
+        };
+        Ok(Rules {
+            topology,
+            diagonal_moves,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Topology {
+    #[default]
+    Flat,
+    // Every row and column is a ring: a tile leaving one edge comes back on the opposite edge
+    Torus,
 }
 
 #[derive(Debug, PartialEq)]
@@ -19,58 +93,129 @@ pub enum GameStatus {
 
 impl Grid {
 
-    fn new(cells: [[u64; 4]; 4]) -> Self {
-        Self { cells, score: 0 }
+    pub fn new(cells: [[u64; 4]; 4]) -> Self {
+        Self::new_with_rules(cells, Rules::default())
+    }
+
+    pub fn new_with_rules(cells: [[u64; 4]; 4], rules: Rules) -> Self {
+        Self {
+            cells,
+            score: 0,
+            last_merges: Vec::new(),
+            last_spawn: None,
+            rules,
+        }
     }
 
      pub fn new_random() -> Self {
+        Self::new_random_with_rules(Rules::default())
+    }
+
+    pub fn new_random_with_rules(rules: Rules) -> Self {
+        Self::new_random_with_rng(rules, &mut rand::thread_rng())
+    }
+
+    // Seeding the random number generator makes the whole game reproducible
+    pub fn new_random_with_rng<R: Rng>(rules: Rules, rng: &mut R) -> Self {
         let cells = [[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]];
-        let mut grid = Self::new(cells);
-        grid.insert_random_cell();
-        grid.insert_random_cell();
+        let mut grid = Self::new_with_rules(cells, rules);
+        grid.insert_random_cell_with_rng(rng);
+        grid.insert_random_cell_with_rng(rng);
         grid
     }
 
     pub fn attempt(&mut self, mov: Move) -> GameStatus {
+        self.attempt_with_rng(mov, &mut rand::thread_rng())
+    }
+
+    pub fn attempt_with_rng<R: Rng>(&mut self, mov: Move, rng: &mut R) -> GameStatus {
         if !self.move_is_valid(mov) {
             return GameStatus::InvalidMove;
         }
 
-        let (new_cells, score_increase) = Self::make_move(self.cells, mov);
+        let (new_cells, merges) = Self::make_move_with_merges(self.cells, mov, self.rules.topology);
         self.cells = new_cells;
-        self.score += score_increase;
+        self.score += merges.iter().sum::<u64>();
+        self.last_merges = merges;
 
-        self.insert_random_cell();
+        self.insert_random_cell_with_rng(rng);
         if self.has_player_lost() {
             return GameStatus::Lost;
         }
         GameStatus::Ok
     }
 
+    // The grid after sliding the tiles, before a new tile spawns. This is what a player
+    // or a solver gets to see of a move. Returns None if the move is invalid.
+    pub fn after_move(&self, mov: Move) -> Option<Grid> {
+        if !self.move_is_valid(mov) {
+            return None;
+        }
+        let (cells, merges) = Self::make_move_with_merges(self.cells, mov, self.rules.topology);
+        Some(Self {
+            cells,
+            score: self.score + merges.iter().sum::<u64>(),
+            last_merges: merges,
+            last_spawn: None,
+            rules: self.rules,
+        })
+    }
+
+    pub fn get_valid_moves(&self) -> Vec<Move> {
+        MOVES
+            .iter()
+            .chain(DIAGONAL_MOVES.iter())
+            .copied()
+            .filter(|mov| self.move_is_valid(*mov))
+            .collect()
+    }
+
     pub fn get_score(&self) -> u64 {
         self.score
     }
 
-    fn insert_random_cell(&mut self) {
+    pub fn get_rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn get_last_merges(&self) -> &[u64] {
+        &self.last_merges
+    }
+
+    pub fn get_last_spawn(&self) -> Option<(usize, usize, u64)> {
+        self.last_spawn
+    }
+
+    // Places a tile coming from outside the game, e.g. garbage sent by an opponent.
+    // Returns false if the cell is already occupied.
+    pub fn insert_cell(&mut self, x: usize, y: usize, value: u64) -> bool {
+        if self.cells[x][y] != 0 {
+            return false;
+        }
+        self.cells[x][y] = value;
+        true
+    }
+
+    fn insert_random_cell_with_rng<R: Rng>(&mut self, rng: &mut R) {
         if self.is_board_full() {
             return;
         }
-        let mut rng = rand::thread_rng();
         let bern = Bernoulli::new(CHANCE_FOR_TWO).unwrap();
-        let roll = bern.sample(&mut rng);
+        let roll = bern.sample(rng);
         let val: u64 = if roll { 2 } else { 4 };
         let empty_cells = Self::get_empty_cells(self.cells);
         // We know it can't be empty because we checked earlier so unwrapping is safe
-        let (x, y) = empty_cells.choose(&mut rng).unwrap();
+        let (x, y) = empty_cells.choose(rng).unwrap();
         self.cells[*x][*y] = val;
+        self.last_spawn = Some((*x, *y, val));
     }
 
-    fn get_empty_cells(cells: [[u64; 4]; 4]) -> Vec<(usize, usize)> {
+    pub fn get_empty_cells(cells: [[u64; 4]; 4]) -> Vec<(usize, usize)> {
         let mut empty_cells: Vec<(usize, usize)> = Vec::new();
 
-        for i in 0..4 {
-            for j in 0..4 {
-                if cells[i][j] == 0 {
+        for (i, row) in cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if *cell == 0 {
                     empty_cells.push((i, j))
                 }
             }
@@ -84,24 +229,105 @@ impl Grid {
     }
 
     fn move_is_valid(&self, mov: Move) -> bool {
-        self.cells != (Self::make_move(self.cells, mov)).0
+        if mov.is_diagonal() && !self.rules.diagonal_moves {
+            return false;
+        }
+        self.cells != (Self::make_move(self.cells, mov, self.rules.topology)).0
     }
 
     pub fn has_player_lost(&self) -> bool {
-        !MOVES.iter().any(|mov| self.move_is_valid(*mov))
+        self.get_valid_moves().is_empty()
+    }
+
+    fn make_move(cells: [[u64; 4]; 4], mov: Move, topology: Topology) -> ([[u64; 4]; 4], u64) {
+        let (cells, merges) = Self::make_move_with_merges(cells, mov, topology);
+        (cells, merges.iter().sum())
+    }
+
+    fn make_move_with_merges(
+        cells: [[u64; 4]; 4],
+        mov: Move,
+        topology: Topology,
+    ) -> ([[u64; 4]; 4], Vec<u64>) {
+        match mov.get_number() {
+            Some(rotation) => Self::handle_move(cells, rotation, topology),
+            None => Self::handle_diagonal_move(cells, mov, topology),
+        }
     }
 
-    fn make_move(cells: [[u64; 4]; 4], mov: Move) -> ([[u64; 4]; 4], u64) {
-        let rotation = mov.get_number();
-        let (cells, score) = Self::handle_move(cells, rotation);
-        (cells, score)
+    // Diagonals can't be reached by rotating the grid, so every diagonal line is read out,
+    // slid towards its end and written back.
+    fn handle_diagonal_move(
+        mut cells: [[u64; 4]; 4],
+        mov: Move,
+        topology: Topology,
+    ) -> ([[u64; 4]; 4], Vec<u64>) {
+        let mut merges = Vec::new();
+        for line in Self::diagonal_lines(mov, topology) {
+            let mut values: Vec<u64> = line.iter().map(|(x, y)| cells[*x][*y]).collect();
+            merges.extend(match topology {
+                Topology::Flat => slide_line(&mut values),
+                Topology::Torus => slide_ring(&mut values),
+            });
+            for ((x, y), value) in line.into_iter().zip(values) {
+                cells[x][y] = value;
+            }
+        }
+        (cells, merges)
+    }
+
+    // Every diagonal running in the direction of the move, ordered so that the move slides
+    // towards the end of each line. On a flat grid the diagonals have lengths 1 to 4. On a
+    // torus every diagonal wraps around into a ring of 4 cells which starts on the top row
+    // when moving down and on the bottom row when moving up.
+    fn diagonal_lines(mov: Move, topology: Topology) -> Vec<Vec<(usize, usize)>> {
+        let (dx, dy): (isize, isize) = match mov {
+            Move::UpLeft => (-1, -1),
+            Move::UpRight => (-1, 1),
+            Move::DownLeft => (1, -1),
+            Move::DownRight => (1, 1),
+            _ => return Vec::new(),
+        };
+        let on_board = |x: isize, y: isize| (0..4).contains(&x) && (0..4).contains(&y);
+        let mut lines = Vec::new();
+        for x in 0..4 {
+            for y in 0..4 {
+                let start = match topology {
+                    Topology::Flat => !on_board(x - dx, y - dy),
+                    Topology::Torus => x == if dx > 0 { 0 } else { 3 },
+                };
+                if !start {
+                    continue;
+                }
+                let mut line = Vec::new();
+                let (mut cx, mut cy) = (x, y);
+                while on_board(cx, cy) && line.len() < 4 {
+                    line.push((cx as usize, cy as usize));
+                    cx += dx;
+                    cy += dy;
+                    if topology == Topology::Torus {
+                        cx = cx.rem_euclid(4);
+                        cy = cy.rem_euclid(4);
+                    }
+                }
+                lines.push(line);
+            }
+        }
+        lines
     }
 
-    fn handle_move(cells: [[u64; 4]; 4], rotation: usize) -> ([[u64; 4]; 4], u64) {
+    fn handle_move(
+        cells: [[u64; 4]; 4],
+        rotation: usize,
+        topology: Topology,
+    ) -> ([[u64; 4]; 4], Vec<u64>) {
         let rotated = Self::rotate_times(cells, rotation);
-        let (cells, score) = Self::mov(rotated);
+        let (cells, merges) = match topology {
+            Topology::Flat => Self::mov(rotated),
+            Topology::Torus => Self::mov_torus(rotated),
+        };
         let rotated_back = Self::rotate_times(cells, 4 - rotation);
-        (rotated_back, score)
+        (rotated_back, merges)
     }
 
     fn rotate_times(cells: [[u64; 4]; 4], n: usize) -> [[u64; 4]; 4] {
@@ -112,59 +338,114 @@ impl Grid {
         rotated_cells
     }
 
-    fn mov(cells: [[u64; 4]; 4]) -> ([[u64; 4]; 4], u64) {
+    fn mov(cells: [[u64; 4]; 4]) -> ([[u64; 4]; 4], Vec<u64>) {
         // Implementation of Going right.
         let mut cells = Self::mov_all_cells_to_the_side(cells);
-        let mut score_increase: u64 = 0;
-
-        for i in 0..4 {
-            let old_row = cells[i];
-            let mut new_row = old_row;
+        let mut merges = Vec::new();
+        for row in cells.iter_mut() {
+            merges.extend(merge_line(row));
+        }
+        (Self::mov_all_cells_to_the_side(cells), merges)
+    }
 
-            for j in (1..=3).rev() {
-                if new_row[j] == old_row[j - 1] {
-                    new_row[j] *= 2;
-                    score_increase += new_row[j];
-                    new_row[j - 1] = 0;
-                }
-            }
-            cells[i] = new_row;
+    fn mov_torus(mut cells: [[u64; 4]; 4]) -> ([[u64; 4]; 4], Vec<u64>) {
+        // Implementation of Going right when every row is a ring.
+        let mut merges = Vec::new();
+        for row in cells.iter_mut() {
+            merges.extend(slide_ring(row));
         }
-        (Self::mov_all_cells_to_the_side(cells), score_increase)
+        (cells, merges)
     }
 
     fn mov_all_cells_to_the_side(mut cells: [[u64; 4]; 4]) -> [[u64; 4]; 4] {
-        for i in 0..4 {
-            let mut row = cells[i];
-            for j in (0..3).rev() {
-                let temp = row[j];
-                row[j] = 0;
-                let mut index = j;
-                while index < 3 {
-                    if row[index + 1] != 0 {
-                        break;
-                    }
-                    index += 1;
-                }
-                row[index] = temp;
-            }
-            cells[i] = row;
+        for row in cells.iter_mut() {
+            mov_line_to_the_side(row);
         }
         cells
     }
 
-    fn rotate(mut cells: [[u64; 4]; 4]) -> [[u64; 4]; 4] {
-        for i in 0..4 {
-            for j in i..4 {
-                let temp = cells[i][j];
-                cells[i][j] = cells[j][i];
-                cells[j][i] = temp;
+    fn rotate(cells: [[u64; 4]; 4]) -> [[u64; 4]; 4] {
+        let mut rotated = [[0; 4]; 4];
+        for (i, row) in cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                rotated[j][i] = *cell;
             }
         }
-        for i in 0..4 {
-            cells[i].reverse()
+        for row in rotated.iter_mut() {
+            row.reverse()
         }
-        cells
+        rotated
+    }
+}
+
+// Slides a line of any length towards its end, merging every tile at most once.
+// Returns the values of the tiles created by merges.
+pub(crate) fn slide_line(line: &mut [u64]) -> Vec<u64> {
+    mov_line_to_the_side(line);
+    let merges = merge_line(line);
+    mov_line_to_the_side(line);
+    merges
+}
+
+// Merges equal neighbours of a line whose tiles are already moved to the side
+fn merge_line(line: &mut [u64]) -> Vec<u64> {
+    let old_line = line.to_vec();
+    let mut merges = Vec::new();
+
+    for j in (1..line.len()).rev() {
+        if line[j] == old_line[j - 1] && line[j] != 0 {
+            line[j] *= 2;
+            merges.push(line[j]);
+            line[j - 1] = 0;
+        }
+    }
+    merges
+}
+
+// Slides a ring towards the end of the slice, where the end of the slice wraps around to
+// its start. Returns the values of the tiles created by merges.
+pub(crate) fn slide_ring(ring: &mut [u64]) -> Vec<u64> {
+    let len = ring.len();
+    let start = ring_start(ring);
+    let mut line: Vec<u64> = (0..len).map(|i| ring[(start + i) % len]).collect();
+    let merges = slide_line(&mut line);
+    for (i, value) in line.into_iter().enumerate() {
+        ring[(start + i) % len] = value;
+    }
+    merges
+}
+
+// A ring has no edge to slide against, so it's cut open into an ordinary line which is
+// then slid towards its end. The line normally starts at the first cell. If a block of
+// tiles wraps around from the last cell to the first cell, the line starts at the first
+// empty cell instead, so the block stays together at the end of the line. A full ring
+// is cut one cell later when the two tiles across the edge are equal, so they merge.
+fn ring_start(ring: &[u64]) -> usize {
+    let last = match ring.last() {
+        Some(last) => *last,
+        None => return 0,
+    };
+    match ring.iter().position(|cell| *cell == 0) {
+        Some(first_empty) if last != 0 => first_empty,
+        Some(_) => 0,
+        None if last == ring[0] => 1,
+        None => 0,
+    }
+}
+
+fn mov_line_to_the_side(line: &mut [u64]) {
+    let last = line.len().saturating_sub(1);
+    for j in (0..last).rev() {
+        let temp = line[j];
+        line[j] = 0;
+        let mut index = j;
+        while index < last {
+            if line[index + 1] != 0 {
+                break;
+            }
+            index += 1;
+        }
+        line[index] = temp;
     }
 }
 
@@ -174,28 +455,131 @@ impl Default for Grid {
     }
 }
 
-#[derive(Clone, Copy)]
+// Position notation: the rows from top to bottom separated by '/', the cells of a row
+// separated by ',', 0 for empty, e.g. "2,0,0,0/0,4,0,0/0,0,0,0/0,0,0,2".
+// Only the tiles are written, not the score or the rules.
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<String> = self
+            .cells
+            .iter()
+            .map(|row| row.map(|cell| cell.to_string()).join(","))
+            .collect();
+        write!(f, "{}", rows.join("/"))
+    }
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid position '{}'", notation);
+        let rows: Vec<&str> = notation.trim().split('/').collect();
+        if rows.len() != 4 {
+            return Err(invalid());
+        }
+        let mut cells = [[0; 4]; 4];
+        for (row, text) in cells.iter_mut().zip(rows) {
+            let values: Vec<&str> = text.split(',').collect();
+            if values.len() != 4 {
+                return Err(invalid());
+            }
+            for (cell, value) in row.iter_mut().zip(values) {
+                let value: u64 = value.trim().parse().map_err(|_| invalid())?;
+                if value == 1 || !(value == 0 || value.is_power_of_two()) {
+                    return Err(invalid());
+                }
+                *cell = value;
+            }
+        }
+        Ok(Grid::new(cells))
+    }
+}
+
+// Serialized by its notation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
 pub enum Move {
     Left,
     Right,
     Up,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl Move {
-    fn get_number(&self) -> usize {
+    // Number of rotations turning the move into a move to the right
+    fn get_number(&self) -> Option<usize> {
         match self {
-            Move::Right => 0,
-            Move::Up => 1,
-            Move::Left => 2,
-            Move::Down => 3,
+            Move::Right => Some(0),
+            Move::Up => Some(1),
+            Move::Left => Some(2),
+            Move::Down => Some(3),
+            _ => None,
+        }
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        self.get_number().is_none()
+    }
+}
+
+// Move notation: L, R, U, D, UL, UR, DL and DR. Parsing also takes the full names, in
+// any case.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let notation = match self {
+            Move::Left => "L",
+            Move::Right => "R",
+            Move::Up => "U",
+            Move::Down => "D",
+            Move::UpLeft => "UL",
+            Move::UpRight => "UR",
+            Move::DownLeft => "DL",
+            Move::DownRight => "DR",
+        };
+        write!(f, "{}", notation)
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        match notation.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "l" | "left" => Ok(Move::Left),
+            "r" | "right" => Ok(Move::Right),
+            "u" | "up" => Ok(Move::Up),
+            "d" | "down" => Ok(Move::Down),
+            "ul" | "upleft" => Ok(Move::UpLeft),
+            "ur" | "upright" => Ok(Move::UpRight),
+            "dl" | "downleft" => Ok(Move::DownLeft),
+            "dr" | "downright" => Ok(Move::DownRight),
+            _ => Err(format!("unknown move '{}'", notation)),
         }
     }
 }
 
+impl From<Move> for String {
+    fn from(mov: Move) -> Self {
+        mov.to_string()
+    }
+}
+
+impl TryFrom<String> for Move {
+    type Error = String;
+
+    fn try_from(notation: String) -> Result<Self, Self::Error> {
+        notation.parse()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Grid, Move};
+    use super::{slide_line, Grid, Move, Rules, Topology, DIAGONAL_MOVES, MOVES, RULE_NAMES};
 
     #[test]
     fn get_empty_cells_work() {
@@ -458,7 +842,7 @@ mod tests {
         let row4 = [2, 0, 0, 0];
         let result_grid = [row1, row2, row3, row4];
 
-        let (grid, _) = Grid::make_move(grid, Move::Left);
+        let (grid, _) = Grid::make_move(grid, Move::Left, Topology::Flat);
         assert_eq!(grid, result_grid)
     }
 
@@ -476,7 +860,7 @@ mod tests {
         let row4 = [0, 0, 0, 0];
         let result_grid = [row1, row2, row3, row4];
 
-        let (grid, _) = Grid::make_move(grid, Move::Up);
+        let (grid, _) = Grid::make_move(grid, Move::Up, Topology::Flat);
         assert_eq!(grid, result_grid)
     }
 
@@ -494,7 +878,7 @@ mod tests {
         let row4 = [4, 4, 4, 2];
         let result_grid = [row1, row2, row3, row4];
 
-        let (grid, _) = Grid::make_move(grid, Move::Down);
+        let (grid, _) = Grid::make_move(grid, Move::Down, Topology::Flat);
         assert_eq!(grid, result_grid)
     }
 
@@ -560,7 +944,7 @@ mod tests {
         let row4 = [2, 2, 2, 2];
         let grid = Grid::new([row1, row2, row3, row4]);
 
-        assert_eq!(grid.is_board_full(), true);
+        assert!(grid.is_board_full());
 
         let row1 = [2, 2, 4, 2];
         let row2 = [2, 2, 0, 2];
@@ -568,7 +952,7 @@ mod tests {
         let row4 = [2, 2, 2, 2];
         let grid = Grid::new([row1, row2, row3, row4]);
 
-        assert_eq!(grid.is_board_full(), false)
+        assert!(!grid.is_board_full())
     }
 
     #[test]
@@ -578,7 +962,7 @@ mod tests {
         let row3 = [4, 2, 2, 2];
         let row4 = [2, 2, 2, 2];
         let mut grid = Grid::new([row1, row2, row3, row4]);
-        grid.insert_random_cell();
+        grid.insert_random_cell_with_rng(&mut rand::thread_rng());
 
         let cell = grid.cells[1][1];
         assert!(cell == 2 || cell == 4)
@@ -690,9 +1074,23 @@ mod tests {
         let row3 = [0, 0, 0, 0];
         let row4 = [0, 8, 8, 0];
         let grid = [row1, row2, row3, row4];
-        let (_, score) = Grid::mov(grid);
+        let (_, merges) = Grid::mov(grid);
+
+        assert_eq!(merges.iter().sum::<u64>(), 24)
+    }
 
-        assert_eq!(score, 24)
+    #[test]
+    fn slide_line_works_for_other_lengths() {
+        let mut line = [2, 2, 2];
+        assert_eq!(slide_line(&mut line), vec![4]);
+        assert_eq!(line, [0, 2, 4]);
+
+        let mut line = [4, 0, 4, 8, 8];
+        assert_eq!(slide_line(&mut line), vec![16, 8]);
+        assert_eq!(line, [0, 0, 0, 8, 16]);
+
+        let mut line: [u64; 0] = [];
+        assert!(slide_line(&mut line).is_empty());
     }
 
     #[test]
@@ -709,4 +1107,328 @@ mod tests {
 
         assert_eq!(grid.get_score(), 4+4+8)
     }
+
+    #[test]
+    fn last_merges_are_recorded_after_attempt() {
+        let row1 = [2, 2, 4, 4];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [32, 32, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        grid.attempt(Move::Right);
+
+        let mut merges = grid.get_last_merges().to_vec();
+        merges.sort();
+        assert_eq!(merges, vec![4, 8, 64])
+    }
+
+    #[test]
+    fn insert_cell_only_fills_empty_cells() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+
+        assert!(!grid.insert_cell(0, 0, 4));
+        assert!(grid.insert_cell(3, 1, 4));
+        assert_eq!(grid.cells[0][0], 2);
+        assert_eq!(grid.cells[3][1], 4);
+    }
+
+    #[test]
+    fn torus_move_right_works_like_flat_without_wrapping_blocks() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 2, 0, 2];
+        let row3 = [4, 4, 2, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [0, 0, 0, 2];
+        let row2 = [0, 0, 0, 4];
+        let row3 = [0, 0, 8, 2];
+        let row4 = [0, 0, 0, 0];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, _) = Grid::make_move(grid, Move::Right, Topology::Torus);
+        assert_eq!(grid, result_grid)
+    }
+
+    #[test]
+    fn torus_merges_across_the_edge() {
+        let row1 = [2, 0, 4, 2];
+        let row2 = [4, 0, 0, 4];
+        let row3 = [2, 8, 0, 16];
+        let row4 = [0, 0, 0, 0];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [4, 0, 0, 4];
+        let row2 = [8, 0, 0, 0];
+        let row3 = [2, 8, 0, 16];
+        let row4 = [0, 0, 0, 0];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, score) = Grid::make_move(grid, Move::Right, Topology::Torus);
+        assert_eq!(grid, result_grid);
+        assert_eq!(score, 4 + 8)
+    }
+
+    #[test]
+    fn torus_full_ring_merges_each_tile_once() {
+        let row1 = [2, 2, 2, 2];
+        let row2 = [2, 4, 8, 2];
+        let row3 = [2, 4, 8, 16];
+        let row4 = [4, 4, 8, 2];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [4, 0, 0, 4];
+        let row2 = [4, 0, 4, 8];
+        let row3 = [2, 4, 8, 16];
+        let row4 = [0, 8, 8, 2];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, score) = Grid::make_move(grid, Move::Right, Topology::Torus);
+        assert_eq!(grid, result_grid);
+        assert_eq!(score, 8 + 4 + 8)
+    }
+
+    #[test]
+    fn torus_move_left_wraps_the_other_way() {
+        let row1 = [2, 0, 0, 2];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [0, 0, 0, 4];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, _) = Grid::make_move(grid, Move::Left, Topology::Torus);
+        assert_eq!(grid, result_grid)
+    }
+
+    #[test]
+    fn torus_move_is_valid_when_only_a_wrapped_merge_is_possible() {
+        let row1 = [2, 4, 8, 2];
+        let row2 = [16, 32, 64, 128];
+        let row3 = [2, 4, 8, 16];
+        let row4 = [32, 64, 128, 256];
+        let flat = Grid::new([row1, row2, row3, row4]);
+        let mut torus = Grid::new([row1, row2, row3, row4]);
+        torus.rules = Rules {
+            topology: Topology::Torus,
+            ..Rules::default()
+        };
+
+        assert!(!flat.move_is_valid(Move::Right));
+        assert!(torus.move_is_valid(Move::Right));
+        assert!(torus.move_is_valid(Move::Left));
+        assert!(!torus.move_is_valid(Move::Up));
+    }
+
+    #[test]
+    fn torus_player_has_lost_only_when_no_ring_has_equal_neighbours() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [2, 4, 2, 4];
+        let row4 = [4, 2, 4, 2];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        grid.rules = Rules {
+            topology: Topology::Torus,
+            ..Rules::default()
+        };
+        assert!(grid.has_player_lost());
+
+        // The first and last column are neighbours on a torus
+        grid.cells[0] = [8, 4, 2, 8];
+        assert!(!grid.has_player_lost());
+        assert!(Grid::new(grid.cells).has_player_lost());
+    }
+
+    #[test]
+    fn diagonal_lines_cover_the_grid() {
+        for mov in super::DIAGONAL_MOVES {
+            let flat = Grid::diagonal_lines(mov, Topology::Flat);
+            let mut lengths: Vec<usize> = flat.iter().map(|line| line.len()).collect();
+            lengths.sort();
+            assert_eq!(lengths, vec![1, 1, 2, 2, 3, 3, 4]);
+
+            let torus = Grid::diagonal_lines(mov, Topology::Torus);
+            assert!(torus.iter().all(|line| line.len() == 4));
+            assert_eq!(torus.len(), 4);
+        }
+    }
+
+    #[test]
+    fn move_down_right_works() {
+        let row1 = [2, 0, 0, 4];
+        let row2 = [0, 2, 0, 0];
+        let row3 = [2, 0, 2, 0];
+        let row4 = [0, 0, 0, 2];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [0, 0, 0, 4];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 4, 0];
+        let row4 = [0, 2, 0, 4];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, score) = Grid::make_move(grid, Move::DownRight, Topology::Flat);
+        assert_eq!(grid, result_grid);
+        assert_eq!(score, 8)
+    }
+
+    #[test]
+    fn move_up_left_merges_each_tile_once() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 2, 0, 0];
+        let row3 = [0, 0, 2, 0];
+        let row4 = [0, 0, 0, 2];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [4, 0, 0, 0];
+        let row2 = [0, 4, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, _) = Grid::make_move(grid, Move::UpLeft, Topology::Flat);
+        assert_eq!(grid, result_grid)
+    }
+
+    #[test]
+    fn diagonal_moves_need_the_rule() {
+        let row1 = [0, 0, 0, 0];
+        let row2 = [0, 2, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+
+        assert_eq!(grid.attempt(Move::UpRight), super::GameStatus::InvalidMove);
+        grid.rules.diagonal_moves = true;
+        assert!(grid.move_is_valid(Move::UpRight));
+    }
+
+    #[test]
+    fn player_has_not_lost_when_only_a_diagonal_move_is_possible() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [2, 4, 2, 4];
+        let row4 = [4, 2, 4, 2];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        assert!(grid.has_player_lost());
+
+        grid.rules.diagonal_moves = true;
+        assert!(!grid.has_player_lost());
+    }
+
+    #[test]
+    fn torus_diagonal_moves_wrap_around() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 2];
+        let grid = [row1, row2, row3, row4];
+
+        let row1 = [4, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let result_grid = [row1, row2, row3, row4];
+
+        let (grid, _) = Grid::make_move(grid, Move::DownRight, Topology::Torus);
+        assert_eq!(grid, result_grid)
+    }
+
+    #[test]
+    fn after_move_slides_without_spawning() {
+        let row1 = [2, 2, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 4];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let after = grid.after_move(Move::Right).unwrap();
+        assert_eq!(after.cells, [[0, 0, 0, 4], row2, row3, row4]);
+        assert_eq!(after.get_score(), 4);
+        assert!(grid.after_move(Move::DownRight).is_none());
+    }
+
+    #[test]
+    fn get_valid_moves_works() {
+        let row1 = [2, 4, 0, 0];
+        let row2 = [4, 2, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        assert_eq!(grid.get_valid_moves(), vec![Move::Right, Move::Down]);
+    }
+
+    #[test]
+    fn seeded_games_are_reproducible() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let play = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut grid = Grid::new_random_with_rng(Rules::default(), &mut rng);
+            for mov in [Move::Left, Move::Up, Move::Right, Move::Down, Move::Left] {
+                grid.attempt_with_rng(mov, &mut rng);
+            }
+            grid
+        };
+
+        assert_eq!(play(7), play(7));
+    }
+
+    #[test]
+    fn rules_round_trip_through_their_names() {
+        for name in RULE_NAMES {
+            let rules: Rules = name.parse().unwrap();
+            assert_eq!(rules.to_string(), name);
+        }
+        assert_eq!("classic".parse::<Rules>(), Ok(Rules::default()));
+        assert!("hex".parse::<Rules>().is_err());
+    }
+
+    #[test]
+    fn positions_round_trip_through_their_notation() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 4, 0, 0];
+        let row3 = [0, 0, 1024, 0];
+        let row4 = [0, 0, 0, 2];
+        let grid = Grid::new([row1, row2, row3, row4]);
+        assert_eq!(grid.to_string(), "2,0,0,0/0,4,0,0/0,0,1024,0/0,0,0,2");
+        assert_eq!(grid.to_string().parse::<Grid>(), Ok(grid));
+
+        assert!("2,0,0,0/0,0,0,0/0,0,0,0".parse::<Grid>().is_err());
+        assert!("3,0,0,0/0,0,0,0/0,0,0,0/0,0,0,0".parse::<Grid>().is_err());
+    }
+
+    #[test]
+    fn moves_round_trip_through_their_notation() {
+        for mov in MOVES.iter().chain(DIAGONAL_MOVES.iter()) {
+            assert_eq!(mov.to_string().parse::<Move>(), Ok(*mov));
+        }
+        assert_eq!("Down-Left".parse::<Move>(), Ok(Move::DownLeft));
+        assert!("sideways".parse::<Move>().is_err());
+    }
+
+    #[test]
+    fn attempts_remember_the_spawn() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        assert_eq!(grid.get_last_spawn(), None);
+
+        grid.attempt(Move::Right);
+        let (x, y, value) = grid.get_last_spawn().unwrap();
+        assert_eq!(grid.cells[x][y], value);
+        assert!(grid.after_move(Move::Left).unwrap().get_last_spawn().is_none());
+    }
 }
@@ -0,0 +1,299 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::grid::Grid;
+
+// Weights of the snake pattern: the largest tiles should run along the rows in a zigzag
+// ending in the top left corner.
+const SNAKE_WEIGHTS: [[f64; 4]; 4] = [
+    [15.0, 14.0, 13.0, 12.0],
+    [8.0, 9.0, 10.0, 11.0],
+    [7.0, 6.0, 5.0, 4.0],
+    [0.0, 1.0, 2.0, 3.0],
+];
+
+// Scores a position from the point of view of the player. Higher is better. The value may
+// only depend on the tiles and the score, the search reuses it for every position with the
+// same ones.
+pub trait Heuristic {
+    fn evaluate(&self, grid: &Grid) -> f64;
+}
+
+impl<F: Fn(&Grid) -> f64> Heuristic for F {
+    fn evaluate(&self, grid: &Grid) -> f64 {
+        self(grid)
+    }
+}
+
+// Board features, all measured on log2 of the tile values except for the score
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    Score,
+    EmptyCells,
+    // Zero when every row and column is sorted, more negative the more they zigzag
+    Monotonicity,
+    // Zero when neighbouring tiles are equal, more negative the more they differ
+    Smoothness,
+    // The size of the largest tile when it sits in a corner, zero otherwise
+    MaxTileInCorner,
+    MergesAvailable,
+    SnakePattern,
+}
+
+pub const FEATURES: [Feature; 7] = [
+    Feature::Score,
+    Feature::EmptyCells,
+    Feature::Monotonicity,
+    Feature::Smoothness,
+    Feature::MaxTileInCorner,
+    Feature::MergesAvailable,
+    Feature::SnakePattern,
+];
+
+impl Feature {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Feature::Score => "score",
+            Feature::EmptyCells => "empty_cells",
+            Feature::Monotonicity => "monotonicity",
+            Feature::Smoothness => "smoothness",
+            Feature::MaxTileInCorner => "max_tile_in_corner",
+            Feature::MergesAvailable => "merges_available",
+            Feature::SnakePattern => "snake_pattern",
+        }
+    }
+
+    pub fn measure(&self, grid: &Grid) -> f64 {
+        let ranks = ranks(&grid.cells);
+        match self {
+            Feature::Score => grid.get_score() as f64,
+            Feature::EmptyCells => Grid::get_empty_cells(grid.cells).len() as f64,
+            Feature::Monotonicity => lines(&ranks).iter().map(monotonicity).sum(),
+            Feature::Smoothness => -neighbours(&ranks)
+                .filter(|(a, b)| *a != 0.0 && *b != 0.0)
+                .map(|(a, b)| (a - b).abs())
+                .sum::<f64>(),
+            Feature::MaxTileInCorner => {
+                let max = ranks.iter().flatten().fold(0.0, |max: f64, rank| max.max(*rank));
+                let corners = [ranks[0][0], ranks[0][3], ranks[3][0], ranks[3][3]];
+                if corners.contains(&max) {
+                    max
+                } else {
+                    0.0
+                }
+            }
+            Feature::MergesAvailable => neighbours(&ranks)
+                .filter(|(a, b)| *a != 0.0 && a == b)
+                .count() as f64,
+            Feature::SnakePattern => ranks
+                .iter()
+                .flatten()
+                .zip(SNAKE_WEIGHTS.iter().flatten())
+                .map(|(rank, weight)| rank * weight)
+                .sum(),
+        }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = ConfigError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        FEATURES
+            .iter()
+            .find(|feature| feature.get_name() == name)
+            .copied()
+            .ok_or_else(|| ConfigError::UnknownFeature(name.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    UnknownFeature(String),
+    InvalidLine(usize, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "couldn't read weights: {}", error),
+            ConfigError::UnknownFeature(name) => write!(f, "unknown feature '{}'", name),
+            ConfigError::InvalidLine(number, line) => {
+                write!(f, "line {}: expected 'feature = weight', got '{}'", number, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// A weighted sum of board features. This is the one place heuristics get tuned, so every
+// solver and every position evaluation shown to the player should go through it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedHeuristic {
+    weights: Vec<(Feature, f64)>,
+}
+
+impl WeightedHeuristic {
+    pub fn new(weights: Vec<(Feature, f64)>) -> Self {
+        Self { weights }
+    }
+
+    // Reads weights written one per line as `feature = weight`. Empty lines and lines
+    // starting with # are ignored. Features that aren't mentioned get no weight.
+    pub fn from_config(config: &str) -> Result<Self, ConfigError> {
+        let mut weights = Vec::new();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || ConfigError::InvalidLine(i + 1, line.to_string());
+            let (name, weight) = line.split_once('=').ok_or_else(invalid)?;
+            let feature: Feature = name.trim().parse()?;
+            let weight: f64 = weight.trim().parse().map_err(|_| invalid())?;
+            weights.push((feature, weight));
+        }
+        Ok(Self::new(weights))
+    }
+
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let config = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_config(&config)
+    }
+
+    pub fn get_weights(&self) -> &[(Feature, f64)] {
+        &self.weights
+    }
+
+    // The weighted value of every feature, e.g. to show what makes a position dangerous
+    pub fn explain(&self, grid: &Grid) -> Vec<(Feature, f64)> {
+        self.weights
+            .iter()
+            .map(|(feature, weight)| (*feature, weight * feature.measure(grid)))
+            .collect()
+    }
+}
+
+impl Heuristic for WeightedHeuristic {
+    fn evaluate(&self, grid: &Grid) -> f64 {
+        self.explain(grid).iter().map(|(_, value)| value).sum()
+    }
+}
+
+impl Default for WeightedHeuristic {
+    fn default() -> Self {
+        Self::new(vec![
+            (Feature::Score, 1.0),
+            (Feature::EmptyCells, 270.0),
+            (Feature::Monotonicity, 47.0),
+            (Feature::Smoothness, 10.0),
+            (Feature::MaxTileInCorner, 20.0),
+            (Feature::MergesAvailable, 70.0),
+        ])
+    }
+}
+
+// log2 of every tile, 0 for empty cells
+fn ranks(cells: &[[u64; 4]; 4]) -> [[f64; 4]; 4] {
+    cells.map(|row| row.map(|cell| if cell == 0 { 0.0 } else { (cell as f64).log2() }))
+}
+
+// Every row and every column
+fn lines(ranks: &[[f64; 4]; 4]) -> Vec<[f64; 4]> {
+    let columns = (0..4).map(|y| [ranks[0][y], ranks[1][y], ranks[2][y], ranks[3][y]]);
+    ranks.iter().copied().chain(columns).collect()
+}
+
+// Every pair of horizontally or vertically neighbouring cells
+fn neighbours(ranks: &[[f64; 4]; 4]) -> impl Iterator<Item = (f64, f64)> + '_ {
+    let horizontal = (0..4).flat_map(move |x| (0..3).map(move |y| (ranks[x][y], ranks[x][y + 1])));
+    let vertical = (0..3).flat_map(move |x| (0..4).map(move |y| (ranks[x][y], ranks[x + 1][y])));
+    horizontal.chain(vertical)
+}
+
+// Penalty of the direction, increasing or decreasing, the line is closest to being sorted in
+fn monotonicity(line: &[f64; 4]) -> f64 {
+    let (mut increasing, mut decreasing) = (0.0, 0.0);
+    for pair in line.windows(2) {
+        if pair[0] > pair[1] {
+            increasing += pair[0] - pair[1];
+        } else {
+            decreasing += pair[1] - pair[0];
+        }
+    }
+    -f64::min(increasing, decreasing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigError, Feature, Heuristic, WeightedHeuristic};
+    use crate::grid::Grid;
+
+    fn grid() -> Grid {
+        let row1 = [8, 4, 2, 0];
+        let row2 = [4, 4, 0, 0];
+        let row3 = [2, 0, 0, 0];
+        let row4 = [0, 0, 0, 2];
+        Grid::new([row1, row2, row3, row4])
+    }
+
+    #[test]
+    fn features_measure_the_grid() {
+        let grid = grid();
+        assert_eq!(Feature::EmptyCells.measure(&grid), 9.0);
+        assert_eq!(Feature::MaxTileInCorner.measure(&grid), 3.0);
+        // 4 next to 4 in the second row and in the second column
+        assert_eq!(Feature::MergesAvailable.measure(&grid), 2.0);
+        // Every row and column is sorted
+        assert_eq!(Feature::Monotonicity.measure(&grid), 0.0);
+        // |3-2| + |2-1| + |2-2| + |3-2| + |2-2| + |2-1|
+        assert_eq!(Feature::Smoothness.measure(&grid), -4.0);
+    }
+
+    #[test]
+    fn zigzag_is_not_monotonic() {
+        let row1 = [2, 8, 2, 8];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+        assert_eq!(Feature::Monotonicity.measure(&grid), -2.0);
+    }
+
+    #[test]
+    fn weighted_heuristic_sums_weighted_features() {
+        let heuristic = WeightedHeuristic::new(vec![
+            (Feature::EmptyCells, 10.0),
+            (Feature::MergesAvailable, -1.0),
+        ]);
+        assert_eq!(heuristic.evaluate(&grid()), 90.0 - 2.0);
+    }
+
+    #[test]
+    fn weights_are_read_from_config() {
+        let config = "
+            # Only care about space
+            empty_cells = 2.5
+            snake_pattern=0.1
+        ";
+        let heuristic = WeightedHeuristic::from_config(config).unwrap();
+        assert_eq!(
+            heuristic.get_weights(),
+            &[(Feature::EmptyCells, 2.5), (Feature::SnakePattern, 0.1)]
+        );
+    }
+
+    #[test]
+    fn bad_config_is_rejected() {
+        let unknown = WeightedHeuristic::from_config("corners = 1");
+        assert!(matches!(unknown, Err(ConfigError::UnknownFeature(name)) if name == "corners"));
+
+        let invalid = WeightedHeuristic::from_config("score = 1\nempty_cells 2");
+        assert!(matches!(invalid, Err(ConfigError::InvalidLine(2, _))));
+
+        let weight = WeightedHeuristic::from_config("score = lots");
+        assert!(matches!(weight, Err(ConfigError::InvalidLine(1, _))));
+    }
+}
@@ -0,0 +1,239 @@
+use rand::{distributions::Bernoulli, prelude::Distribution, seq::SliceRandom};
+
+use crate::grid::{slide_line, GameStatus, CHANCE_FOR_TWO};
+
+pub const HEX_MOVES: [HexMove; 6] = [
+    HexMove::Up,
+    HexMove::UpRight,
+    HexMove::DownRight,
+    HexMove::Down,
+    HexMove::DownLeft,
+    HexMove::UpLeft,
+];
+
+// The six directions of a board made of flat-topped hexagons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HexMove {
+    Up,
+    UpRight,
+    DownRight,
+    Down,
+    DownLeft,
+    UpLeft,
+}
+
+impl HexMove {
+    // Step in axial coordinates (q, r)
+    fn get_direction(&self) -> (i32, i32) {
+        match self {
+            HexMove::Up => (0, -1),
+            HexMove::UpRight => (1, -1),
+            HexMove::DownRight => (1, 0),
+            HexMove::Down => (0, 1),
+            HexMove::DownLeft => (-1, 1),
+            HexMove::UpLeft => (-1, 0),
+        }
+    }
+}
+
+// Hexagon shaped board of hexagonal cells, addressed with axial coordinates (q, r).
+// A board of radius 2 has 19 cells, a board of radius 3 has 37.
+#[derive(PartialEq, Debug)]
+pub struct HexGrid {
+    radius: i32,
+    coords: Vec<(i32, i32)>,
+    cells: Vec<u64>,
+    score: u64,
+}
+
+impl HexGrid {
+    fn new(radius: i32) -> Self {
+        let mut coords = Vec::new();
+        for q in -radius..=radius {
+            for r in -radius..=radius {
+                if (q + r).abs() <= radius {
+                    coords.push((q, r));
+                }
+            }
+        }
+        let cells = vec![0; coords.len()];
+        Self {
+            radius,
+            coords,
+            cells,
+            score: 0,
+        }
+    }
+
+    pub fn new_random(radius: i32) -> Self {
+        let mut grid = Self::new(radius);
+        grid.insert_random_cell();
+        grid.insert_random_cell();
+        grid
+    }
+
+    pub fn get_radius(&self) -> i32 {
+        self.radius
+    }
+
+    pub fn get_score(&self) -> u64 {
+        self.score
+    }
+
+    // All cells of the board together with their axial coordinates
+    pub fn iter_cells(&self) -> impl Iterator<Item = ((i32, i32), u64)> + '_ {
+        self.coords.iter().copied().zip(self.cells.iter().copied())
+    }
+
+    pub fn attempt(&mut self, mov: HexMove) -> GameStatus {
+        let (new_cells, score_increase) = self.make_move(mov);
+        if new_cells == self.cells {
+            return GameStatus::InvalidMove;
+        }
+        self.cells = new_cells;
+        self.score += score_increase;
+
+        self.insert_random_cell();
+        if self.has_player_lost() {
+            return GameStatus::Lost;
+        }
+        GameStatus::Ok
+    }
+
+    pub fn has_player_lost(&self) -> bool {
+        HEX_MOVES
+            .iter()
+            .all(|mov| self.make_move(*mov).0 == self.cells)
+    }
+
+    fn index(&self, q: i32, r: i32) -> Option<usize> {
+        if q.abs() > self.radius || r.abs() > self.radius || (q + r).abs() > self.radius {
+            return None;
+        }
+        self.coords.iter().position(|coord| *coord == (q, r))
+    }
+
+    fn insert_random_cell(&mut self) {
+        let empty_cells: Vec<usize> = (0..self.cells.len())
+            .filter(|i| self.cells[*i] == 0)
+            .collect();
+        let mut rng = rand::thread_rng();
+        if let Some(i) = empty_cells.choose(&mut rng) {
+            let bern = Bernoulli::new(CHANCE_FOR_TWO).unwrap();
+            self.cells[*i] = if bern.sample(&mut rng) { 2 } else { 4 };
+        }
+    }
+
+    // Every line of cells running in the direction of the move. Each line starts at the
+    // edge opposite to the move, so sliding it towards its end is the move itself.
+    fn lines(&self, mov: HexMove) -> Vec<Vec<usize>> {
+        let (dq, dr) = mov.get_direction();
+        let mut lines = Vec::new();
+        for (q, r) in self.coords.iter() {
+            if self.index(q - dq, r - dr).is_some() {
+                continue;
+            }
+            let mut line = Vec::new();
+            let (mut q, mut r) = (*q, *r);
+            while let Some(i) = self.index(q, r) {
+                line.push(i);
+                q += dq;
+                r += dr;
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn make_move(&self, mov: HexMove) -> (Vec<u64>, u64) {
+        let mut cells = self.cells.clone();
+        let mut score_increase = 0;
+        for line in self.lines(mov) {
+            let mut values: Vec<u64> = line.iter().map(|i| cells[*i]).collect();
+            score_increase += slide_line(&mut values).iter().sum::<u64>();
+            for (i, value) in line.iter().zip(values) {
+                cells[*i] = value;
+            }
+        }
+        (cells, score_increase)
+    }
+}
+
+impl Default for HexGrid {
+    fn default() -> Self {
+        Self::new_random(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HexGrid, HexMove};
+    use crate::grid::GameStatus;
+
+    fn grid_with(radius: i32, tiles: &[((i32, i32), u64)]) -> HexGrid {
+        let mut grid = HexGrid::new(radius);
+        for ((q, r), value) in tiles {
+            let i = grid.index(*q, *r).unwrap();
+            grid.cells[i] = *value;
+        }
+        grid
+    }
+
+    #[test]
+    fn boards_have_the_right_number_of_cells() {
+        assert_eq!(HexGrid::new(2).cells.len(), 19);
+        assert_eq!(HexGrid::new(3).cells.len(), 37);
+    }
+
+    #[test]
+    fn lines_cover_the_board_once_in_every_direction() {
+        let grid = HexGrid::new(2);
+        for mov in super::HEX_MOVES {
+            let lines = grid.lines(mov);
+            let mut lengths: Vec<usize> = lines.iter().map(|line| line.len()).collect();
+            lengths.sort();
+            assert_eq!(lengths, vec![3, 3, 4, 4, 5]);
+        }
+    }
+
+    #[test]
+    fn move_down_slides_along_the_column() {
+        let grid = grid_with(2, &[((0, -2), 2), ((0, 0), 2), ((0, 1), 4)]);
+        let (cells, score) = grid.make_move(HexMove::Down);
+
+        let expected = grid_with(2, &[((0, 1), 4), ((0, 2), 4)]);
+        assert_eq!(cells, expected.cells);
+        assert_eq!(score, 4);
+    }
+
+    #[test]
+    fn move_up_right_merges_each_tile_once() {
+        // The diagonal line from (-2, 2) to (2, -2)
+        let tiles = [((-2, 2), 2), ((-1, 1), 2), ((0, 0), 2), ((1, -1), 2)];
+        let grid = grid_with(2, &tiles);
+        let (cells, score) = grid.make_move(HexMove::UpRight);
+
+        let expected = grid_with(2, &[((1, -1), 4), ((2, -2), 4)]);
+        assert_eq!(cells, expected.cells);
+        assert_eq!(score, 8);
+    }
+
+    #[test]
+    fn move_against_the_edge_is_invalid() {
+        let mut grid = grid_with(2, &[((0, 2), 2), ((-1, 2), 4)]);
+        assert_eq!(grid.attempt(HexMove::Down), GameStatus::InvalidMove);
+    }
+
+    #[test]
+    fn player_has_lost_on_a_full_board_without_merges() {
+        // Three values alternating so that no two neighbours are equal
+        let mut grid = HexGrid::new(2);
+        for (i, (q, r)) in grid.coords.clone().into_iter().enumerate() {
+            grid.cells[i] = [2, 4, 8][(q - r).rem_euclid(3) as usize];
+        }
+        assert!(grid.has_player_lost());
+
+        grid.cells[0] = 0;
+        assert!(!grid.has_player_lost());
+    }
+}
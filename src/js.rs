@@ -0,0 +1,299 @@
+// Headless game for JavaScript, so other frontends and bots can play by the same rules
+// without the yew app. Build without the `ui` feature to leave the app out of the module.
+//
+//     const game = new Game(42);
+//     game.onMerge(({ tiles, score }) => console.log("merged", tiles, score));
+//     game.onGameOver(({ cells, score }) => console.log("game over", score));
+//     game.move("left");
+
+use std::cell::RefCell;
+
+use js_sys::{Function, Object, Reflect, Uint32Array};
+use rand::{rngs::StdRng, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{GameStatus, Grid, Move, Rules};
+use crate::replay::Replay;
+use crate::solver::Expectimax;
+
+const DEFAULT_HINT_DEPTH: usize = 3;
+
+// Every export takes `&self`, so wasm-bindgen only holds shared borrows of the game and
+// listeners can call back into it. The state is only borrowed mutably outside of them.
+#[wasm_bindgen]
+pub struct Game {
+    state: RefCell<State>,
+    on_merge: RefCell<Option<Function>>,
+    on_game_over: RefCell<Option<Function>>,
+}
+
+struct State {
+    grid: Grid,
+    rng: StdRng,
+    replay: Replay,
+    // Positions before every move, for undo
+    history: Vec<Grid>,
+}
+
+// The logic is kept apart from the JS conversions, so it also runs in native tests
+impl Game {
+    fn create(seed: Option<u32>, rules: Option<String>) -> Result<Self, String> {
+        let rules = match rules {
+            Some(rules) => rules.parse()?,
+            None => Rules::default(),
+        };
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed as u64),
+            None => StdRng::from_entropy(),
+        };
+        let grid = Grid::new_random_with_rng(rules, &mut rng);
+        Ok(Self::from_grid(grid, rng, None))
+    }
+
+    fn from_grid(grid: Grid, rng: StdRng, replay: Option<Replay>) -> Self {
+        let state = State {
+            replay: replay.unwrap_or_else(|| Replay::new(&grid)),
+            grid,
+            rng,
+            history: Vec::new(),
+        };
+        Self {
+            state: RefCell::new(state),
+            on_merge: RefCell::new(None),
+            on_game_over: RefCell::new(None),
+        }
+    }
+
+    // Plays the move and returns its status with the values of the merged tiles
+    fn play(&self, mov: Move) -> (GameStatus, Vec<u32>) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let before = state.grid.clone();
+        let status = state.grid.attempt_with_rng(mov, &mut state.rng);
+        if status == GameStatus::InvalidMove {
+            return (status, Vec::new());
+        }
+        state.history.push(before);
+        state.replay.record(mov, &state.grid);
+        let merges = state
+            .grid
+            .get_last_merges()
+            .iter()
+            .map(|tile| *tile as u32)
+            .collect();
+        (status, merges)
+    }
+
+    fn load(json: &str, seed: Option<u32>) -> Result<Self, String> {
+        let replay = Replay::from_json(json).map_err(|error| error.to_string())?;
+        let mut positions = replay.positions().map_err(|error| error.to_string())?;
+        let grid = positions.pop().unwrap();
+        let rng = StdRng::seed_from_u64(seed.unwrap_or_default() as u64);
+        let game = Self::from_grid(grid, rng, Some(replay));
+        game.state.borrow_mut().history = positions;
+        Ok(game)
+    }
+
+    // The payload of the listeners: the board and score after the move, and the merged
+    // tiles for the merge listener
+    fn event(&self, tiles: Option<&[u32]>) -> Result<JsValue, JsValue> {
+        let event = Object::new();
+        if let Some(tiles) = tiles {
+            Reflect::set(&event, &"tiles".into(), &Uint32Array::from(tiles))?;
+        }
+        let cells = self.cells();
+        Reflect::set(&event, &"cells".into(), &Uint32Array::from(&cells[..]))?;
+        Reflect::set(&event, &"score".into(), &self.score().into())?;
+        Ok(event.into())
+    }
+}
+
+fn notify(
+    listener: Option<Function>,
+    event: impl FnOnce() -> Result<JsValue, JsValue>,
+) -> Result<(), JsValue> {
+    match listener {
+        Some(listener) => listener.call1(&JsValue::NULL, &event()?).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    // A new game, seeded for reproducible spawns. Rules are "classic", "wrap", "diagonal"
+    // or "wrap-diagonal".
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: Option<u32>, rules: Option<String>) -> Result<Game, JsError> {
+        Self::create(seed, rules).map_err(|error| JsError::new(&error))
+    }
+
+    // A game saved by `serialize`, with its history
+    pub fn deserialize(json: &str, seed: Option<u32>) -> Result<Game, JsError> {
+        Self::load(json, seed).map_err(|error| JsError::new(&error))
+    }
+
+    // Plays a move, e.g. "left", "L" or "up-right", and returns the status like `status`.
+    // Then calls the merge and game over listeners, which may read or play the game.
+    #[wasm_bindgen(js_name = move)]
+    pub fn attempt(&self, direction: &str) -> Result<String, JsValue> {
+        let mov: Move = direction
+            .parse()
+            .map_err(|error: String| JsError::new(&error))?;
+        let (status, merges) = self.play(mov);
+        // Cloned, so a listener can replace the listeners
+        if !merges.is_empty() {
+            let listener = self.on_merge.borrow().clone();
+            notify(listener, || self.event(Some(&merges)))?;
+        }
+        if status == GameStatus::Lost {
+            let listener = self.on_game_over.borrow().clone();
+            notify(listener, || self.event(None))?;
+        }
+        Ok(self.status())
+    }
+
+    // The 16 tile values, row by row, 0 for empty cells
+    pub fn cells(&self) -> Vec<u32> {
+        self.state
+            .borrow()
+            .grid
+            .cells
+            .iter()
+            .flatten()
+            .map(|cell| *cell as u32)
+            .collect()
+    }
+
+    pub fn score(&self) -> f64 {
+        self.state.borrow().grid.get_score() as f64
+    }
+
+    // "playing" or "lost"
+    pub fn status(&self) -> String {
+        if self.state.borrow().grid.has_player_lost() {
+            "lost".to_string()
+        } else {
+            "playing".to_string()
+        }
+    }
+
+    #[wasm_bindgen(js_name = validMoves)]
+    pub fn valid_moves(&self) -> Vec<String> {
+        self.state
+            .borrow()
+            .grid
+            .get_valid_moves()
+            .iter()
+            .map(|mov| mov.to_string())
+            .collect()
+    }
+
+    // Takes back the last move. Returns false when there is none.
+    pub fn undo(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        match state.history.pop() {
+            Some(grid) => {
+                state.grid = grid;
+                state.replay.steps.pop();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The whole game as replay JSON
+    pub fn serialize(&self) -> String {
+        self.state.borrow().replay.to_json()
+    }
+
+    // The expectimax solver's move, or undefined when the game is over
+    pub fn hint(&self, depth: Option<usize>) -> Option<String> {
+        Expectimax::default()
+            .with_depth(depth.unwrap_or(DEFAULT_HINT_DEPTH))
+            .best_move(&self.state.borrow().grid)
+            .map(|mov| mov.to_string())
+    }
+
+    // Called with `{ tiles, cells, score }` after every move that merges, where tiles are
+    // the values of the merged tiles
+    #[wasm_bindgen(js_name = onMerge)]
+    pub fn on_merge(&self, callback: Option<Function>) {
+        *self.on_merge.borrow_mut() = callback;
+    }
+
+    // Called with `{ cells, score }` when a move loses the game
+    #[wasm_bindgen(js_name = onGameOver)]
+    pub fn on_game_over(&self, callback: Option<Function>) {
+        *self.on_game_over.borrow_mut() = callback;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Game;
+    use crate::grid::Move;
+
+    #[test]
+    fn undo_and_serialize() {
+        let game = Game::create(Some(3), Some("wrap".to_string())).unwrap();
+        let start = game.cells();
+        let mut played = 0;
+        for mov in [Move::Left, Move::Up, Move::Right, Move::Down] {
+            if game.valid_moves().contains(&mov.to_string()) {
+                game.play(mov);
+                played += 1;
+            }
+        }
+        assert!(played > 0);
+
+        let copy = Game::load(&game.serialize(), None).unwrap();
+        assert_eq!(copy.cells(), game.cells());
+        assert_eq!(copy.score(), game.score());
+
+        while game.undo() {}
+        assert_eq!(game.cells(), start);
+        assert_eq!(game.score(), 0.0);
+        assert_eq!(game.state.borrow().replay.len(), 0);
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        assert!(Game::create(None, Some("hex".to_string())).is_err());
+        assert!(Game::load("{}", None).is_err());
+        let game = Game::create(Some(1), None).unwrap();
+        assert_eq!(game.status(), "playing");
+        assert!(game.hint(Some(1)).is_some());
+    }
+}
+
+// Listeners are called from JS, through the bindings, to catch borrows that are still held
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use js_sys::Function;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Game;
+
+    #[wasm_bindgen_test]
+    fn listeners_read_the_game() {
+        let game = Game::new(Some(5), None).unwrap();
+        let test = Function::new_with_args(
+            "game",
+            r#"
+            const events = [];
+            const record = (event) => events.push([game.cells(), game.score(), event]);
+            game.onMerge(record);
+            game.onGameOver(record);
+            const moves = ["left", "up", "right", "down"];
+            for (let i = 0; game.status() === "playing"; i++) {
+                game.move(moves[i % 4]);
+            }
+            return events.length > 0 && events.every(([cells, score, event]) =>
+                cells.join() === event.cells.join() && score === event.score);
+            "#,
+        );
+        let result = test.call1(&JsValue::NULL, &JsValue::from(game)).unwrap();
+        assert_eq!(result.as_bool(), Some(true));
+    }
+}
@@ -1,9 +1,33 @@
+#[cfg(feature = "ui")]
 use model::Model;
+#[cfg(feature = "ui")]
 use wasm_bindgen::prelude::wasm_bindgen;
 
+#[cfg(feature = "ui")]
 mod model;
-mod grid;
+pub mod agent;
+pub mod batch;
+pub mod benchmark;
+pub mod bitboard;
+pub mod cube;
+pub mod env;
+pub mod estimate;
+pub mod grid;
+pub mod heuristic;
+pub mod hex;
+pub mod js;
+pub mod monte_carlo;
+pub mod ntuple;
+pub mod palette;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod replay;
+pub mod review;
+pub mod solver;
+pub mod tablebase;
+pub mod versus;
 
+#[cfg(feature = "ui")]
 #[wasm_bindgen(start)]
 fn main() {
     console_error_panic_hook::set_once();
@@ -1,44 +1,232 @@
 use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::JsCast;
-use web_sys::HtmlDivElement;
+use web_sys::{HtmlDivElement, HtmlInputElement, HtmlSelectElement};
 use web_sys::{TouchEvent, TouchList};
 use yew::events::KeyboardEvent;
 use yew::prelude::*;
 
-use crate::grid::{Grid, Move};
+use crate::agent::{Agent, CornerAgent, CyclicAgent, GreedyAgent, RandomAgent};
+use crate::cube::{Cube, CubeMove};
+use crate::estimate::{Estimate, Estimator};
+use crate::grid::{GameStatus, Grid, Move, Rules, Topology};
+use crate::heuristic::WeightedHeuristic;
+use crate::hex::{HexGrid, HexMove, HEX_MOVES};
+use crate::monte_carlo::MonteCarlo;
+use crate::palette::{get_color_for_cell, get_color_for_text};
+use crate::replay::Replay;
+use crate::review::{Review, Reviewer};
+use crate::solver::{Analysis, Expectimax};
+use crate::versus::{Player, Versus};
 
-fn get_color_for_cell(value: u64) -> &'static str {
-    match value {
-        0 => "rgba(238, 228, 218, 0.35)",
-        2 => "#eee4da",
-        4 => "#ede0c8",
-        8 => "#f2b179",
-        16 => "#f59563",
-        32 => "#f67c5f",
-        64 => "#f65e3b",
-        128 => "#edcf72",
-        256 => "#edcc61",
-        512 => "#edc850",
-        1024 => "#edc53f",
-        2048 => "#edc22e",
-        _ => "#3c3a32",
+// Size in pixels of a hexagonal cell, measured from corner to corner
+const HEX_CELL_SIZE: f64 = 70.0;
+// The hexagonal boards on offer, 19 and 37 cells
+const HEX_RADII: [i32; 2] = [2, 3];
+// Hints search as deep as they can within this many milliseconds
+const HINT_TIME_BUDGET_MS: f64 = 250.0;
+const HINT_MAX_DEPTH: usize = 6;
+// Moves per second when watching the AI
+const DEFAULT_AUTOPLAY_SPEED: f64 = 4.0;
+const MAX_AUTOPLAY_SPEED: f64 = 20.0;
+// The expectimax autoplayer gets less time per move than a hint, to keep up with the speed
+const AUTOPLAY_TIME_BUDGET_MS: f64 = 40.0;
+const REVIEW_TIME_BUDGET_MS: f64 = 30.0;
+// The odds are estimated from this many rollouts, a batch per timer tick
+const ESTIMATE_ROLLOUTS: usize = 200;
+const ESTIMATE_BATCH: usize = 10;
+// The danger indicator shows this many of the weakest features of the position
+const DANGER_FEATURES: usize = 3;
+
+// The agents that can play the game when watching the AI
+#[derive(Clone, Copy, PartialEq)]
+enum AgentKind {
+    Expectimax,
+    MonteCarlo,
+    Greedy,
+    Corner,
+    Cyclic,
+    Random,
+}
+
+const AGENT_KINDS: [AgentKind; 6] = [
+    AgentKind::Expectimax,
+    AgentKind::MonteCarlo,
+    AgentKind::Greedy,
+    AgentKind::Corner,
+    AgentKind::Cyclic,
+    AgentKind::Random,
+];
+
+impl AgentKind {
+    fn get_name(&self) -> &'static str {
+        match self {
+            AgentKind::Expectimax => "Expectimax",
+            AgentKind::MonteCarlo => "Monte Carlo",
+            AgentKind::Greedy => "Greedy",
+            AgentKind::Corner => "Corner",
+            AgentKind::Cyclic => "Cyclic",
+            AgentKind::Random => "Random",
+        }
+    }
+
+    // The expectimax agent searches with the same heuristic as the hints
+    fn create(&self, heuristic: &WeightedHeuristic) -> Box<dyn Agent> {
+        let seed = rand::random();
+        match self {
+            AgentKind::Expectimax => Box::new(
+                Expectimax::new(heuristic.clone())
+                    .with_depth(HINT_MAX_DEPTH)
+                    .with_time_budget(AUTOPLAY_TIME_BUDGET_MS),
+            ),
+            AgentKind::MonteCarlo => Box::new(MonteCarlo::new(seed).with_playouts(20)),
+            AgentKind::Greedy => Box::new(GreedyAgent::new(seed)),
+            AgentKind::Corner => Box::new(CornerAgent),
+            AgentKind::Cyclic => Box::new(CyclicAgent::default()),
+            AgentKind::Random => Box::new(RandomAgent::new(seed)),
+        }
+    }
+}
+
+// A recurring browser timer that is cleared when dropped
+struct Interval {
+    id: i32,
+    _callback: Closure<dyn FnMut()>,
+}
+
+impl Interval {
+    fn new(milliseconds: i32, callback: impl FnMut() + 'static) -> Self {
+        let callback = Closure::wrap(Box::new(callback) as Box<dyn FnMut()>);
+        let id = web_sys::window()
+            .unwrap()
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                callback.as_ref().unchecked_ref(),
+                milliseconds,
+            )
+            .unwrap();
+        Self {
+            id,
+            _callback: callback,
+        }
     }
 }
 
-fn get_color_for_text(value: u64) -> &'static str {
-    match value {
-        2 => "#6c6462",
-        4 => "#6c6462",
-        _ => "#FFFFFF",
+impl Drop for Interval {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.id);
+        }
     }
 }
 
+fn get_arrow(mov: Move) -> &'static str {
+    match mov {
+        Move::Left => "←",
+        Move::Right => "→",
+        Move::Up => "↑",
+        Move::Down => "↓",
+        Move::UpLeft => "↖",
+        Move::UpRight => "↗",
+        Move::DownLeft => "↙",
+        Move::DownRight => "↘",
+    }
+}
+
+// Swipes shorter than this many pixels in both directions are ignored
+const SWIPE_THRESHOLD: i32 = 30;
+
+// The move of a swipe, by its direction on the screen. Swipes close to 45 degrees are
+// diagonal moves when those are allowed.
+fn get_swipe_move(dx: i32, dy: i32, diagonal_moves: bool) -> Move {
+    let is_diagonal = dx.abs().min(dy.abs()) * 5 > dx.abs().max(dy.abs()) * 2;
+    if is_diagonal && diagonal_moves {
+        match (dx.is_positive(), dy.is_positive()) {
+            (false, false) => Move::UpLeft,
+            (true, false) => Move::UpRight,
+            (false, true) => Move::DownLeft,
+            (true, true) => Move::DownRight,
+        }
+    } else if dx.abs() > dy.abs() {
+        if dx.is_positive() {
+            Move::Right
+        } else {
+            Move::Left
+        }
+    } else if dy.is_positive() {
+        Move::Down
+    } else {
+        Move::Up
+    }
+}
+
+// Flat-topped hexagons have a neighbour every 60 degrees, starting straight up, in the
+// order of `HEX_MOVES`
+fn get_hex_swipe_move(dx: i32, dy: i32) -> HexMove {
+    let angle = (-dy as f64).atan2(dx as f64).to_degrees();
+    let sector = ((90.0 - angle) / 60.0).round().rem_euclid(6.0) as usize;
+    HEX_MOVES[sector]
+}
+
+// An agent playing the second board of a versus game, one move per timer tick
+struct Opponent {
+    agent: Box<dyn Agent>,
+    _timer: Interval,
+}
+
+// A game being reviewed. One move is reviewed per timer tick, so the page stays
+// responsive while the review fills in.
+struct ReviewState {
+    positions: Vec<Grid>,
+    review: Review,
+    // Index of the position on the board
+    selected: usize,
+    timer: Option<Interval>,
+}
+
+// Odds for the position in `grid`, refined in the background until enough rollouts
+// have been played
+struct EstimateState {
+    grid: Grid,
+    estimate: Estimate,
+    estimator: Estimator,
+    timer: Option<Interval>,
+}
+
 pub enum Msg {
     KeyDown(KeyboardEvent),
     TouchStart(TouchEvent),
-    TouchMove(TouchEvent),
+    TouchMove,
     TouchEnd(TouchEvent),
     NewGame,
+    Hint,
+    ComputeHint,
+    ToggleAutoplay,
+    StepAutoplay,
+    SetAutoplaySpeed(f64),
+    SetAgent(usize),
+    StartReview,
+    ReviewNextMove,
+    SelectPosition(usize),
+    CloseReview,
+    ToggleEstimate,
+    ToggleDanger,
+    StepEstimate,
+    PlayClassic,
+    PlayWrap,
+    PlayDiagonal,
+    PlayVersus,
+    PlayVersusAgent,
+    StepOpponent,
+    PlayHex(i32),
+    PlayCube,
+    MoveCube(CubeMove),
+}
+
+// The game being played. The classic game is always kept in `Model::grid`.
+enum Mode {
+    Classic,
+    Versus(Box<Versus>),
+    Hex(HexGrid),
+    Cube(Box<Cube>),
 }
 
 pub struct Model {
@@ -46,9 +234,133 @@ pub struct Model {
     grid_node: NodeRef,
     touch_start_x: Option<i32>,
     touch_start_y: Option<i32>,
+    mode: Mode,
+    hint: Option<Analysis>,
+    hint_pending: bool,
+    agent_kind: AgentKind,
+    agent: Box<dyn Agent>,
+    // Running while the AI plays
+    autoplay: Option<Interval>,
+    autoplay_speed: f64,
+    // Every move of the classic game so far
+    replay: Replay,
+    review: Option<ReviewState>,
+    show_estimate: bool,
+    estimate: Option<EstimateState>,
+    // Evaluates positions for the hints and the danger indicator
+    heuristic: WeightedHeuristic,
+    show_danger: bool,
+    // Plays the second versus board instead of a second person, using `agent_kind`
+    opponent: Option<Opponent>,
 }
 
 impl Model {
+    fn view_board(&self, cells: &[[u64; 4]; 4]) -> Html {
+        html! {
+            <div class="board">
+                <div class="square-grid">
+                    { for cells.iter().enumerate().map(|(y, row)| self.view_row((y, row))) }
+                </div>
+            </div>
+        }
+    }
+
+    fn view_versus_board(&self, versus: &Versus, player: Player) -> Html {
+        let (name, keys) = match (player, &self.opponent) {
+            (Player::One, None) => ("Player 1", "WASD"),
+            (Player::Two, None) => ("Player 2", "Arrows"),
+            (Player::One, Some(_)) => ("You", "WASD or arrows"),
+            (Player::Two, Some(_)) => ("AI", self.agent_kind.get_name()),
+        };
+        let grid = versus.get_grid(player);
+        html! {
+            <div class="versus-board">
+                <div class="scoreboard">
+                    <div class="score-container">
+                        <h2>{ name }</h2>
+                        <p>{ keys }</p>
+                    </div>
+                    <div class="score-container">
+                        <h2>{ "Score" }</h2>
+                        <p>{ grid.get_score() }</p>
+                    </div>
+                    <div class="score-container">
+                        <h2>{ "Incoming" }</h2>
+                        <p>{ versus.get_pending_garbage(player) }</p>
+                    </div>
+                </div>
+                { self.view_board(&grid.cells) }
+            </div>
+        }
+    }
+
+    // The layers of the cube are shown side by side, from the outermost to the innermost.
+    // Swipes can't go in and out of the screen, so touch screens get buttons for those.
+    fn view_cube(&self, ctx: &Context<Self>, cube: &Cube) -> Html {
+        html! {
+            <>
+            <div class="buttons is-centered">
+                <button onclick={ctx.link().callback(|_| Msg::MoveCube(CubeMove::Out))}>{ "Out (Q)" }</button>
+                <button onclick={ctx.link().callback(|_| Msg::MoveCube(CubeMove::In))}>{ "In (E)" }</button>
+            </div>
+            <div class="cube">
+                {
+                    for cube.cells.iter().enumerate().map(|(z, layer)| html! {
+                        <div class="cube-layer">
+                            <h2>{ format!("Layer {}", z + 1) }</h2>
+                            { self.view_board(layer) }
+                        </div>
+                    })
+                }
+            </div>
+            </>
+        }
+    }
+
+    fn view_hex_board(&self, hex: &HexGrid) -> Html {
+        // Flat-topped hexagons: columns are 3/4 of a cell apart, rows a full cell height
+        let width = HEX_CELL_SIZE;
+        let height = HEX_CELL_SIZE * 3f64.sqrt() / 2.0;
+        let radius = hex.get_radius() as f64;
+        let board_style = format!(
+            "width:{}px;height:{}px;",
+            width * (1.5 * radius + 1.0),
+            height * (2.0 * radius + 1.0)
+        );
+        html! {
+            <div class="hex-board" style={board_style}>
+                {
+                    for hex.iter_cells().map(|((q, r), cell)| {
+                        let left = width * 0.75 * (q as f64 + radius);
+                        let top = height * (r as f64 + q as f64 / 2.0 + radius);
+                        self.view_hex_cell(cell, left, top)
+                    })
+                }
+            </div>
+        }
+    }
+
+    fn view_hex_cell(&self, cell: u64, left: f64, top: f64) -> Html {
+        let style = format!(
+            "background-color:{};left:{}px;top:{}px;width:{}px;height:{}px;",
+            get_color_for_cell(cell),
+            left,
+            top,
+            HEX_CELL_SIZE,
+            HEX_CELL_SIZE * 3f64.sqrt() / 2.0
+        );
+        let cell_text = match cell {
+            0 => "".to_string(),
+            _ => cell.to_string(),
+        };
+        let text_style = format!("color:{};", get_color_for_text(cell));
+        html! {
+            <div class="hex" style={style}>
+                <span class="hex-number" style={text_style}>{ cell_text }</span>
+            </div>
+        }
+    }
+
     fn view_row(&self, (y, row): (usize, &[u64; 4])) -> Html {
         html! {
             <div class="square-row">
@@ -75,8 +387,277 @@ impl Model {
         }
     }
 
+    fn view_hint(&self) -> Html {
+        if self.hint_pending {
+            return html! { <div class="hint">{ "Thinking..." }</div> };
+        }
+        let analysis = match &self.hint {
+            Some(analysis) => analysis,
+            None => return html! {},
+        };
+        html! {
+            <div class="hint">
+                {
+                    for analysis.evaluations.iter().map(|(mov, value)| {
+                        let class = if Some(*mov) == analysis.best_move {
+                            "hint-move best"
+                        } else {
+                            "hint-move"
+                        };
+                        html! {
+                            <span class={class}>{ format!("{} {:.0}", get_arrow(*mov), value) }</span>
+                        }
+                    })
+                }
+                <span class="hint-depth">{ format!("depth {}", analysis.depth) }</span>
+            </div>
+        }
+    }
+
+    fn view_hint_arrow(&self) -> Html {
+        match self.hint.as_ref().and_then(|analysis| analysis.best_move) {
+            Some(mov) => html! { <div class="hint-arrow">{ get_arrow(mov) }</div> },
+            None => html! {},
+        }
+    }
+
+    // The search runs on the main thread, so it is started from a timeout to let the page
+    // show that it's thinking first, and it stops when its time budget runs out.
+    fn request_hint(&mut self, ctx: &Context<Self>) {
+        if self.hint_pending || self.grid.has_player_lost() {
+            return;
+        }
+        self.hint_pending = true;
+        let link = ctx.link().clone();
+        let callback = Closure::once_into_js(move || link.send_message(Msg::ComputeHint));
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(callback.unchecked_ref(), 0)
+            .unwrap();
+    }
+
+    fn view_agent_select(&self, ctx: &Context<Self>) -> Html {
+        let on_agent = ctx.link().callback(|event: Event| {
+            Msg::SetAgent(event.target_unchecked_into::<HtmlSelectElement>().selected_index() as usize)
+        });
+        html! {
+            <select onchange={on_agent}>
+                {
+                    for AGENT_KINDS.iter().map(|kind| html! {
+                        <option selected={*kind == self.agent_kind}>{ kind.get_name() }</option>
+                    })
+                }
+            </select>
+        }
+    }
+
+    fn view_speed(&self, ctx: &Context<Self>) -> Html {
+        let on_speed = ctx.link().callback(|event: InputEvent| {
+            Msg::SetAutoplaySpeed(event.target_unchecked_into::<HtmlInputElement>().value_as_number())
+        });
+        html! {
+            <>
+            <input type="range" min="1" max={MAX_AUTOPLAY_SPEED.to_string()}
+                value={self.autoplay_speed.to_string()} oninput={on_speed} />
+            <span>{ format!("{} moves/s", self.autoplay_speed) }</span>
+            </>
+        }
+    }
+
+    fn view_autoplay(&self, ctx: &Context<Self>) -> Html {
+        let toggle = if self.autoplay.is_some() { "Pause" } else { "Watch AI" };
+        html! {
+            <div class="buttons is-centered autoplay">
+                { self.view_agent_select(ctx) }
+                <button onclick={ctx.link().callback(|_| Msg::ToggleAutoplay)}>{ toggle }</button>
+                <button onclick={ctx.link().callback(|_| Msg::StepAutoplay)}>{ "Step" }</button>
+                { self.view_speed(ctx) }
+            </div>
+        }
+    }
+
+    // The agent and its speed can be changed in the middle of a versus game
+    fn view_opponent(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="buttons is-centered autoplay">
+                { self.view_agent_select(ctx) }
+                { self.view_speed(ctx) }
+            </div>
+        }
+    }
+
+    fn start_opponent(&mut self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let milliseconds = (1000.0 / self.autoplay_speed) as i32;
+        self.opponent = Some(Opponent {
+            agent: self.agent_kind.create(&self.heuristic),
+            _timer: Interval::new(milliseconds, move || link.send_message(Msg::StepOpponent)),
+        });
+    }
+
+    // The distance of the swipe that just ended, if it was long enough to be a move
+    fn take_swipe(&mut self, event: &TouchEvent) -> Option<(i32, i32)> {
+        let x_start = self.touch_start_x.take()?;
+        let y_start = self.touch_start_y.take()?;
+        let touch = event.changed_touches().item(0)?;
+        let dx = touch.client_x() - x_start;
+        let dy = touch.client_y() - y_start;
+        if dx.abs() < SWIPE_THRESHOLD && dy.abs() < SWIPE_THRESHOLD {
+            return None;
+        }
+        Some((dx, dy))
+    }
+
+    fn start_autoplay(&mut self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let milliseconds = (1000.0 / self.autoplay_speed) as i32;
+        self.autoplay = Some(Interval::new(milliseconds, move || {
+            link.send_message(Msg::StepAutoplay)
+        }));
+    }
+
+    // Plays one move for the agent. Returns false when it has nothing left to play.
+    fn step_autoplay(&mut self) -> bool {
+        match self.agent.choose(&self.grid) {
+            Some(mov) => {
+                self.make_move(mov);
+                !self.grid.has_player_lost()
+            }
+            None => false,
+        }
+    }
+
+    fn view_review(&self, ctx: &Context<Self>, state: &ReviewState) -> Html {
+        let moves = &state.review.moves;
+        let selected = state.selected;
+        let last = state.positions.len() - 1;
+        let evaluations = match moves.get(selected) {
+            Some(review) => html! {
+                <div class="review-evaluations">
+                    {
+                        for review.evaluations.iter().map(|(mov, value)| {
+                            let class = match (Some(*mov) == review.best_move, *mov == review.played) {
+                                (true, _) => "hint-move best",
+                                (false, true) => "hint-move played",
+                                _ => "hint-move",
+                            };
+                            html! { <span class={class}>{ format!("{} {:.0}", get_arrow(*mov), value) }</span> }
+                        })
+                    }
+                </div>
+            },
+            None => html! {},
+        };
+        html! {
+            <div class="review">
+                <div class="buttons is-centered">
+                    <span>{ format!("Reviewed {}/{} moves", moves.len(), last) }</span>
+                    <span>{ format!("Mistakes cost {:.0}", state.review.total_cost()) }</span>
+                    <span>{ format!("Blunders {}", state.review.get_blunders().len()) }</span>
+                </div>
+                <div class="buttons is-centered">
+                    <button onclick={ctx.link().callback(move |_| Msg::SelectPosition(selected.saturating_sub(1)))}>{ "Previous" }</button>
+                    <span>{ format!("Position {}/{}", selected, last) }</span>
+                    <button onclick={ctx.link().callback(move |_| Msg::SelectPosition(selected + 1))}>{ "Next" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::CloseReview)}>{ "Close" }</button>
+                </div>
+                { evaluations }
+                <div class="hint-depth">{ state.positions[selected].to_string() }</div>
+                <div class="review-moves">
+                    {
+                        for moves.iter().enumerate().map(|(i, review)| {
+                            let mut class = classes!("review-move");
+                            if review.is_blunder {
+                                class.push("blunder");
+                            }
+                            if i == selected {
+                                class.push("selected");
+                            }
+                            let best = match review.best_move {
+                                Some(best) if best != review.played => format!(" ({})", get_arrow(best)),
+                                _ => "".to_string(),
+                            };
+                            html! {
+                                <span class={class} onclick={ctx.link().callback(move |_| Msg::SelectPosition(i))}>
+                                    { format!("{}.{}{}", i + 1, get_arrow(review.played), best) }
+                                </span>
+                            }
+                        })
+                    }
+                </div>
+            </div>
+        }
+    }
+
+    fn view_estimate(&self) -> Html {
+        let estimate = match &self.estimate {
+            Some(state) => &state.estimate,
+            None => return html! {},
+        };
+        html! {
+            <div class="score-container estimate">
+                <p>{ format!("{}: {:.0}%", estimate.target, estimate.reach_probability() * 100.0) }</p>
+                <p>{ format!("Lost in {} moves: {:.0}%", estimate.horizon, estimate.death_probability() * 100.0) }</p>
+            </div>
+        }
+    }
+
+    // The features of the heuristic that count the least towards the position, so the
+    // player sees what the solver thinks is going wrong
+    fn view_danger(&self) -> Html {
+        if !self.show_danger {
+            return html! {};
+        }
+        let mut features = self.heuristic.explain(&self.grid);
+        features.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        html! {
+            <div class="hint danger">
+                {
+                    for features.iter().take(DANGER_FEATURES).map(|(feature, value)| {
+                        let class = if *value < 0.0 { "danger-feature bad" } else { "danger-feature" };
+                        html! {
+                            <span class={class}>{ format!("{} {:.0}", feature.get_name().replace('_', " "), value) }</span>
+                        }
+                    })
+                }
+            </div>
+        }
+    }
+
+    // Starts estimating again when the grid changed since the last estimate
+    fn refresh_estimate(&mut self, ctx: &Context<Self>) {
+        if !self.show_estimate || !matches!(self.mode, Mode::Classic) {
+            self.estimate = None;
+            return;
+        }
+        if let Some(state) = &self.estimate {
+            if state.grid.cells == self.grid.cells && state.grid.get_rules() == self.grid.get_rules() {
+                return;
+            }
+        }
+        let estimator = Estimator::new(rand::random()).with_rollouts(ESTIMATE_BATCH);
+        let link = ctx.link().clone();
+        self.estimate = Some(EstimateState {
+            grid: self.grid.clone(),
+            estimate: Estimate::new(&self.grid, estimator.get_horizon()),
+            estimator,
+            timer: Some(Interval::new(0, move || link.send_message(Msg::StepEstimate))),
+        });
+    }
+
+    fn start_classic_game(&mut self, rules: Rules) {
+        self.grid = Grid::new_random_with_rules(rules);
+        self.replay = Replay::new(&self.grid);
+        self.hint = None;
+        self.autoplay = None;
+        self.review = None;
+    }
+
     fn make_move(&mut self, mov: Move) {
-        self.grid.attempt(mov);
+        if self.grid.attempt(mov) != GameStatus::InvalidMove {
+            self.replay.record(mov, &self.grid);
+            self.hint = None;
+        }
     }
 }
 
@@ -86,11 +667,27 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_ctx: &Context<Self>) -> Self {
+        let grid = Grid::default();
+        let heuristic = WeightedHeuristic::default();
         let model = Model {
-            grid: Grid::default(),
+            replay: Replay::new(&grid),
+            review: None,
+            show_estimate: false,
+            agent: AgentKind::Expectimax.create(&heuristic),
+            heuristic,
+            show_danger: false,
+            estimate: None,
+            opponent: None,
+            grid,
             grid_node: NodeRef::default(),
             touch_start_x: None,
             touch_start_y: None,
+            mode: Mode::Classic,
+            hint: None,
+            hint_pending: false,
+            agent_kind: AgentKind::Expectimax,
+            autoplay: None,
+            autoplay_speed: DEFAULT_AUTOPLAY_SPEED,
         };
 
         let grid_node = model.grid_node.clone();
@@ -112,12 +709,61 @@ impl Component for Model {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        let game_over_popup = if self.grid.has_player_lost() {
+        let winner = match &self.mode {
+            Mode::Versus(versus) => versus.get_winner(),
+            _ => None,
+        };
+        let has_player_lost = match &self.mode {
+            Mode::Classic => self.grid.has_player_lost(),
+            Mode::Versus(_) => false,
+            Mode::Hex(hex) => hex.has_player_lost(),
+            Mode::Cube(cube) => cube.has_player_lost(),
+        };
+        let score = match &self.mode {
+            Mode::Classic => html! {
+                <div class="score-container"> // Add this wrapper div
+                    <h2>{ "Score" }</h2>
+                    <p>{ self.grid.get_score() }</p>
+                </div>
+            },
+            Mode::Versus(_) => html! {},
+            Mode::Hex(hex) => html! {
+                <div class="score-container">
+                    <h2>{ "Score" }</h2>
+                    <p>{ hex.get_score() }</p>
+                </div>
+            },
+            Mode::Cube(cube) => html! {
+                <div class="score-container">
+                    <h2>{ "Score" }</h2>
+                    <p>{ cube.get_score() }</p>
+                </div>
+            },
+        };
+        let game_over_popup = if let Some(winner) = winner {
+            let text = match (winner, &self.opponent) {
+                (Player::One, None) => "Player 1 Wins",
+                (Player::Two, None) => "Player 2 Wins",
+                (Player::One, Some(_)) => "You Win",
+                (Player::Two, Some(_)) => "The AI Wins",
+            };
+            html! {
+                <div class="game-over-popup">
+                    <div class="game-over-content">
+                        <h2>{ text }</h2>
+                        <button onclick={ctx.link().callback(|_| Msg::NewGame)}>{ "New Game" }</button>
+                    </div>
+                </div>
+            }
+        } else if has_player_lost && self.review.is_none() {
             html! {
                 <div class="game-over-popup">
                     <div class="game-over-content">
                         <h2>{ "Game Over" }</h2>
                         <button onclick={ctx.link().callback(|_| Msg::NewGame)}>{ "New Game" }</button>
+                        if matches!(self.mode, Mode::Classic) {
+                            <button onclick={ctx.link().callback(|_| Msg::StartReview)}>{ "Review" }</button>
+                        }
                     </div>
                 </div>
             }
@@ -128,26 +774,74 @@ impl Component for Model {
         html! {
             <>
             <div class="scoreboard">
-                <div class="score-container"> // Add this wrapper div
-                    <h2>{ "Score" }</h2>
-                    <p>{ self.grid.get_score() }</p>
-                </div>
+                { score }
                 <button onclick={ctx.link().callback(|_| Msg::NewGame)}>{ "New Game" }</button>
+                if matches!(self.mode, Mode::Classic) {
+                    <button onclick={ctx.link().callback(|_| Msg::Hint)}>{ "Hint" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleEstimate)}>{ "Odds" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleDanger)}>{ "Danger" }</button>
+                    { self.view_estimate() }
+                }
+            </div>
+            if let Some(state) = &self.review {
+                { self.view_review(ctx, state) }
+            } else if matches!(self.mode, Mode::Classic) {
+                { self.view_autoplay(ctx) }
+                { self.view_hint() }
+                { self.view_danger() }
+            } else if self.opponent.is_some() {
+                { self.view_opponent(ctx) }
+            }
+            <div class="buttons is-centered">
+                <button onclick={ctx.link().callback(|_| Msg::PlayClassic)}>{ "Classic" }</button>
+                <button onclick={ctx.link().callback(|_| Msg::PlayWrap)}>{ "Wrap" }</button>
+                <button onclick={ctx.link().callback(|_| Msg::PlayDiagonal)}>{ "Diagonal" }</button>
+                <button onclick={ctx.link().callback(|_| Msg::PlayVersus)}>{ "Versus" }</button>
+                <button onclick={ctx.link().callback(|_| Msg::PlayVersusAgent)}>{ "Versus AI" }</button>
+                {
+                    for HEX_RADII.iter().map(|radius| {
+                        let radius = *radius;
+                        let cells = 3 * radius * (radius + 1) + 1;
+                        html! {
+                            <button onclick={ctx.link().callback(move |_| Msg::PlayHex(radius))}>
+                                { format!("Hex {}", cells) }
+                            </button>
+                        }
+                    })
+                }
+                <button onclick={ctx.link().callback(|_| Msg::PlayCube)}>{ "Cube" }</button>
             </div>
             <div class="grid disable-scroll" tabindex="0" ref={self.grid_node.clone()}
-            onkeydown={ctx.link().callback(|event| Msg::KeyDown(event))}
-            ontouchstart={ctx.link().callback(|event| Msg::TouchStart(event))}
-            ontouchmove={ctx.link().callback(|event| Msg::TouchMove(event))}
-            ontouchend={ctx.link().callback(|event| Msg::TouchEnd(event))}
+            onkeydown={ctx.link().callback(Msg::KeyDown)}
+            ontouchstart={ctx.link().callback(Msg::TouchStart)}
+            ontouchmove={ctx.link().callback(|_| Msg::TouchMove)}
+            ontouchend={ctx.link().callback(Msg::TouchEnd)}
             >
             <section class="section">
                 <div class="container">
                     <div class="vcenter">
-                        <div class="board">
-                            <div class="square-grid">
-                                { for self.grid.cells.iter().enumerate().map(|(y, row)| self.view_row((y, row))) }
-                            </div>
-                        </div>
+                        {
+                            match &self.mode {
+                                Mode::Classic if self.review.is_some() => {
+                                    let state = self.review.as_ref().unwrap();
+                                    self.view_board(&state.positions[state.selected].cells)
+                                }
+                                Mode::Classic => html! {
+                                    <div class="hinted-board">
+                                        { self.view_board(&self.grid.cells) }
+                                        { self.view_hint_arrow() }
+                                    </div>
+                                },
+                                Mode::Versus(versus) => html! {
+                                    <>
+                                    { self.view_versus_board(versus, Player::One) }
+                                    { self.view_versus_board(versus, Player::Two) }
+                                    </>
+                                },
+                                Mode::Hex(hex) => self.view_hex_board(hex),
+                                Mode::Cube(cube) => self.view_cube(ctx, cube),
+                            }
+                        }
                     </div>
                 </div>
             </section>
@@ -157,8 +851,81 @@ impl Component for Model {
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
+            Msg::KeyDown(event) if matches!(self.mode, Mode::Versus(_)) => {
+                let key_code = event.key_code();
+                // Against the AI the arrows play the first board too
+                let arrows = match self.opponent {
+                    Some(_) => Player::One,
+                    None => Player::Two,
+                };
+                let player_move = match key_code {
+                    65 => Some((Player::One, Move::Left)),
+                    87 => Some((Player::One, Move::Up)),
+                    68 => Some((Player::One, Move::Right)),
+                    83 => Some((Player::One, Move::Down)),
+                    37 => Some((arrows, Move::Left)),
+                    38 => Some((arrows, Move::Up)),
+                    39 => Some((arrows, Move::Right)),
+                    40 => Some((arrows, Move::Down)),
+                    _ => None,
+                };
+                if let (Mode::Versus(versus), Some((player, mov))) = (&mut self.mode, player_move) {
+                    versus.attempt(player, mov);
+                }
+
+                true
+            }
+            Msg::KeyDown(event) if matches!(self.mode, Mode::Hex(_)) => {
+                let key_code = event.key_code();
+                let hex_move = match key_code {
+                    81 => Some(HexMove::UpLeft),
+                    87 => Some(HexMove::Up),
+                    69 => Some(HexMove::UpRight),
+                    65 => Some(HexMove::DownLeft),
+                    83 => Some(HexMove::Down),
+                    68 => Some(HexMove::DownRight),
+                    _ => None,
+                };
+                if let (Mode::Hex(hex), Some(mov)) = (&mut self.mode, hex_move) {
+                    hex.attempt(mov);
+                }
+
+                true
+            }
+            Msg::KeyDown(event) if matches!(self.mode, Mode::Cube(_)) => {
+                let key_code = event.key_code();
+                let cube_move = match key_code {
+                    37 => Some(CubeMove::Left),
+                    38 => Some(CubeMove::Up),
+                    39 => Some(CubeMove::Right),
+                    40 => Some(CubeMove::Down),
+                    81 => Some(CubeMove::Out),
+                    69 => Some(CubeMove::In),
+                    _ => None,
+                };
+                if let (Mode::Cube(cube), Some(mov)) = (&mut self.mode, cube_move) {
+                    cube.attempt(mov);
+                }
+
+                true
+            }
+            Msg::KeyDown(event) if self.review.is_some() => {
+                if let Some(state) = &self.review {
+                    match event.key_code() {
+                        37 => ctx.link().send_message(Msg::SelectPosition(state.selected.saturating_sub(1))),
+                        39 => ctx.link().send_message(Msg::SelectPosition(state.selected + 1)),
+                        27 => ctx.link().send_message(Msg::CloseReview),
+                        _ => {}
+                    }
+                }
+                false
+            }
+            Msg::KeyDown(event) if event.key_code() == 72 => {
+                self.request_hint(ctx);
+                true
+            }
             Msg::KeyDown(event) => {
                 let key_code = event.key_code();
                 let arrow = match key_code {
@@ -166,9 +933,15 @@ impl Component for Model {
                     38 => Some(Move::Up),
                     39 => Some(Move::Right),
                     40 => Some(Move::Down),
+                    // Numpad 7, 9, 1 and 3, with and without num lock
+                    103 | 36 => Some(Move::UpLeft),
+                    105 | 33 => Some(Move::UpRight),
+                    97 | 35 => Some(Move::DownLeft),
+                    99 | 34 => Some(Move::DownRight),
                     _ => None,
                 };
                 if let Some(a) = arrow {
+                    self.autoplay = None;
                     self.make_move(a);
                 }
 
@@ -183,50 +956,256 @@ impl Component for Model {
                 }
                 true
             }
-            Msg::TouchMove(_) => true,
+            Msg::TouchMove => true,
+            Msg::TouchEnd(_) if self.review.is_some() => false,
             Msg::TouchEnd(event) => {
-                match (self.touch_start_x, self.touch_start_y) {
-                    (None, _) => return false,
-                    (_, None) => return false,
-                    (Some(x_start), Some(y_start)) => {
-                        let changed_touches: TouchList = event.changed_touches();
-                        if changed_touches.length() == 0 {
-                            return false;
-                        }
-                        // Can't be empty because we checked;
-                        let touch = changed_touches.item(0).unwrap();
-                        let dx = touch.client_x() - x_start;
-                        let dy = touch.client_y() - y_start;
-                        let move_threshold = 30;
-                        if dx.abs() < move_threshold && dy.abs() < move_threshold {
-                            return false;
-                        }
-
-                        let mov = if dx.abs() > dy.abs() {
-                            if dx.is_positive() {
-                                Move::Right
-                            } else {
-                                Move::Left
-                            }
-                        } else {
-                            if dy.is_positive() {
-                                Move::Down
-                            } else {
-                                Move::Up
-                            }
+                let Some((dx, dy)) = self.take_swipe(&event) else {
+                    return false;
+                };
+                match &mut self.mode {
+                    Mode::Classic => {
+                        let mov = get_swipe_move(dx, dy, self.grid.get_rules().diagonal_moves);
+                        self.autoplay = None;
+                        self.make_move(mov);
+                    }
+                    // Both people can't swipe on one screen, so only the game against the AI
+                    // is played by touch
+                    Mode::Versus(versus) if self.opponent.is_some() => {
+                        versus.attempt(Player::One, get_swipe_move(dx, dy, false));
+                    }
+                    Mode::Hex(hex) => {
+                        hex.attempt(get_hex_swipe_move(dx, dy));
+                    }
+                    Mode::Cube(cube) => {
+                        let mov = match get_swipe_move(dx, dy, false) {
+                            Move::Left => CubeMove::Left,
+                            Move::Right => CubeMove::Right,
+                            Move::Up => CubeMove::Up,
+                            _ => CubeMove::Down,
                         };
-                        self.grid.attempt(mov);
-
-                        self.touch_start_x = None;
-                        self.touch_start_y = None;
+                        cube.attempt(mov);
                     }
+                    // Swipes must not reach the classic game hidden behind the other modes
+                    Mode::Versus(_) => return false,
                 }
                 true
             }
             Msg::NewGame => {
-                self.grid = Grid::default();
+                self.hint = None;
+                self.autoplay = None;
+                self.review = None;
+                match &mut self.mode {
+                    Mode::Classic => {
+                        self.grid = Grid::new_random_with_rules(self.grid.get_rules());
+                        self.replay = Replay::new(&self.grid);
+                    }
+                    Mode::Versus(versus) => {
+                        **versus = Versus::default();
+                        if let Some(opponent) = &mut self.opponent {
+                            opponent.agent = self.agent_kind.create(&self.heuristic);
+                        }
+                    }
+                    Mode::Hex(hex) => *hex = HexGrid::new_random(hex.get_radius()),
+                    Mode::Cube(cube) => **cube = Cube::default(),
+                }
+                true
+            }
+            Msg::Hint => {
+                self.request_hint(ctx);
+                true
+            }
+            Msg::ComputeHint => {
+                let solver = Expectimax::new(self.heuristic.clone())
+                    .with_depth(HINT_MAX_DEPTH)
+                    .with_time_budget(HINT_TIME_BUDGET_MS);
+                self.hint = Some(solver.analyze(&self.grid));
+                self.hint_pending = false;
+                true
+            }
+            Msg::ToggleAutoplay => {
+                if self.autoplay.is_some() {
+                    self.autoplay = None;
+                } else if !self.grid.has_player_lost() {
+                    self.start_autoplay(ctx);
+                }
+                true
+            }
+            Msg::StepAutoplay => {
+                if !self.step_autoplay() {
+                    self.autoplay = None;
+                }
+                true
+            }
+            Msg::SetAutoplaySpeed(speed) => {
+                self.autoplay_speed = speed.clamp(1.0, MAX_AUTOPLAY_SPEED);
+                if self.autoplay.is_some() {
+                    self.start_autoplay(ctx);
+                }
+                if self.opponent.is_some() {
+                    self.start_opponent(ctx);
+                }
+                true
+            }
+            Msg::SetAgent(index) => {
+                if let Some(kind) = AGENT_KINDS.get(index) {
+                    self.agent_kind = *kind;
+                    self.agent = kind.create(&self.heuristic);
+                    if let Some(opponent) = &mut self.opponent {
+                        opponent.agent = kind.create(&self.heuristic);
+                    }
+                }
+                true
+            }
+            Msg::StartReview => {
+                // Replays recorded in this session always play back
+                let positions = self.replay.positions().unwrap_or_default();
+                if positions.len() > 1 {
+                    self.hint = None;
+                    self.autoplay = None;
+                    let link = ctx.link().clone();
+                    self.review = Some(ReviewState {
+                        selected: positions.len() - 1,
+                        positions,
+                        review: Review::default(),
+                        timer: Some(Interval::new(0, move || link.send_message(Msg::ReviewNextMove))),
+                    });
+                }
+                true
+            }
+            Msg::ReviewNextMove => {
+                let Some(state) = &mut self.review else {
+                    return false;
+                };
+                let index = state.review.moves.len();
+                match self.replay.steps.get(index) {
+                    Some(step) => {
+                        let reviewer = Reviewer::new(
+                            Expectimax::new(self.heuristic.clone())
+                                .with_depth(HINT_MAX_DEPTH)
+                                .with_time_budget(REVIEW_TIME_BUDGET_MS),
+                        );
+                        let review = reviewer.review_move(&state.positions[index], step.mov);
+                        state.review.moves.push(review);
+                    }
+                    None => state.timer = None,
+                }
+                true
+            }
+            Msg::SelectPosition(index) => {
+                if let Some(state) = &mut self.review {
+                    state.selected = index.min(state.positions.len() - 1);
+                }
+                true
+            }
+            Msg::CloseReview => {
+                self.review = None;
+                true
+            }
+            Msg::ToggleEstimate => {
+                self.show_estimate = !self.show_estimate;
+                true
+            }
+            Msg::ToggleDanger => {
+                self.show_danger = !self.show_danger;
+                true
+            }
+            Msg::StepEstimate => {
+                let Some(state) = &mut self.estimate else {
+                    return false;
+                };
+                let batch = state.estimator.estimate(&state.grid);
+                state.estimate.merge(&batch);
+                if state.estimate.rollouts >= ESTIMATE_ROLLOUTS {
+                    state.timer = None;
+                }
+                true
+            }
+            Msg::PlayClassic => {
+                if self.grid.get_rules() != Rules::default() {
+                    self.start_classic_game(Rules::default());
+                }
+                self.mode = Mode::Classic;
+                self.opponent = None;
+                true
+            }
+            Msg::PlayWrap => {
+                let rules = Rules {
+                    topology: Topology::Torus,
+                    ..Rules::default()
+                };
+                if self.grid.get_rules() != rules {
+                    self.start_classic_game(rules);
+                }
+                self.mode = Mode::Classic;
+                self.opponent = None;
+                true
+            }
+            Msg::PlayDiagonal => {
+                let rules = Rules {
+                    diagonal_moves: true,
+                    ..Rules::default()
+                };
+                if self.grid.get_rules() != rules {
+                    self.start_classic_game(rules);
+                }
+                self.mode = Mode::Classic;
+                self.opponent = None;
+                true
+            }
+            Msg::PlayVersus => {
+                self.autoplay = None;
+                self.review = None;
+                self.opponent = None;
+                self.mode = Mode::Versus(Box::default());
+                true
+            }
+            Msg::PlayVersusAgent => {
+                self.autoplay = None;
+                self.review = None;
+                self.mode = Mode::Versus(Box::default());
+                self.start_opponent(ctx);
+                true
+            }
+            Msg::StepOpponent => {
+                let (Mode::Versus(versus), Some(opponent)) = (&mut self.mode, &mut self.opponent) else {
+                    return false;
+                };
+                if versus.get_winner().is_some() {
+                    return false;
+                }
+                match opponent.agent.choose(versus.get_grid(Player::Two)) {
+                    Some(mov) => {
+                        versus.attempt(Player::Two, mov);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Msg::PlayHex(radius) => {
+                self.autoplay = None;
+                self.review = None;
+                self.opponent = None;
+                self.mode = Mode::Hex(HexGrid::new_random(radius));
+                true
+            }
+            Msg::MoveCube(mov) => {
+                if let Mode::Cube(cube) = &mut self.mode {
+                    cube.attempt(mov);
+                }
+                true
+            }
+            Msg::PlayCube => {
+                self.autoplay = None;
+                self.review = None;
+                self.opponent = None;
+                self.mode = Mode::Cube(Box::default());
                 true
             }
         }
     }
+
+    // The odds follow every change of the grid, whatever caused it
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        self.refresh_estimate(ctx);
+    }
 }
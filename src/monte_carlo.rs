@@ -0,0 +1,258 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::grid::{GameStatus, Grid, Move};
+
+const DEFAULT_PLAYOUTS: usize = 100;
+// Playouts of a move are played in chunks of this many, each with its own seed, so a
+// parallel analysis can spread them over every thread and still get the same totals
+const PLAYOUT_CHUNK: usize = 10;
+
+// How moves are picked during a playout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RolloutPolicy {
+    Random,
+    // The move with the biggest immediate score gain, ties broken at random
+    Greedy,
+}
+
+impl RolloutPolicy {
+    pub fn choose<R: Rng>(&self, grid: &Grid, rng: &mut R) -> Option<Move> {
+        let moves = grid.get_valid_moves();
+        match self {
+            RolloutPolicy::Random => moves.choose(rng).copied(),
+            RolloutPolicy::Greedy => {
+                let gains: Vec<u64> = moves
+                    .iter()
+                    .map(|mov| grid.after_move(*mov).map_or(0, |after| after.get_score()))
+                    .collect();
+                let best = gains.iter().max()?;
+                let best_moves: Vec<Move> = moves
+                    .iter()
+                    .zip(gains.iter())
+                    .filter(|(_, gain)| *gain == best)
+                    .map(|(mov, _)| *mov)
+                    .collect();
+                best_moves.choose(rng).copied()
+            }
+        }
+    }
+}
+
+// Plays every valid move followed by a number of playouts to the end of the game, and
+// picks the move with the best average final score. No heuristic is needed, so it works
+// with every rule variant.
+pub struct MonteCarlo {
+    playouts: usize,
+    policy: RolloutPolicy,
+    // Playouts are cut off after this many moves, if set
+    max_rollout_moves: Option<usize>,
+    rng: StdRng,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+}
+
+impl MonteCarlo {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            playouts: DEFAULT_PLAYOUTS,
+            policy: RolloutPolicy::Random,
+            max_rollout_moves: None,
+            rng: StdRng::seed_from_u64(seed),
+            #[cfg(feature = "parallel")]
+            threads: crate::parallel::default_threads(),
+        }
+    }
+
+    pub fn with_playouts(mut self, playouts: usize) -> Self {
+        self.playouts = playouts.max(1);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RolloutPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_max_rollout_moves(mut self, moves: usize) -> Self {
+        self.max_rollout_moves = Some(moves);
+        self
+    }
+
+    pub fn best_move(&mut self, grid: &Grid) -> Option<Move> {
+        best_of(self.analyze(grid))
+    }
+
+    // Average final score of every valid move. Every move gets its own random number
+    // generator seeded from the main one, so the moves can be played out independently.
+    pub fn analyze(&mut self, grid: &Grid) -> Vec<(Move, f64)> {
+        let moves = grid.get_valid_moves();
+        let seeds: Vec<u64> = moves.iter().map(|_| self.rng.gen()).collect();
+        moves
+            .into_iter()
+            .zip(seeds)
+            .map(|(mov, seed)| (mov, self.average_score(grid, mov, seed)))
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn best_move_parallel(&mut self, grid: &Grid) -> Option<Move> {
+        best_of(self.analyze_parallel(grid))
+    }
+
+    // Same as `analyze`, with the chunks of playouts of every move spread over the threads
+    #[cfg(feature = "parallel")]
+    pub fn analyze_parallel(&mut self, grid: &Grid) -> Vec<(Move, f64)> {
+        let moves = grid.get_valid_moves();
+        let seeds: Vec<u64> = moves.iter().map(|_| self.rng.gen()).collect();
+        let chunks: Vec<(Move, (u64, usize))> = moves
+            .iter()
+            .zip(seeds)
+            .flat_map(|(mov, seed)| self.chunks(seed).into_iter().map(|chunk| (*mov, chunk)))
+            .collect();
+        let bot = &*self;
+        let totals = crate::parallel::map(&chunks, self.threads, |(mov, (seed, playouts))| {
+            bot.play_chunk(grid, *mov, *seed, *playouts)
+        });
+        moves
+            .into_iter()
+            .map(|mov| {
+                let total: u64 = chunks
+                    .iter()
+                    .zip(totals.iter())
+                    .filter(|((chunk_move, _), _)| *chunk_move == mov)
+                    .map(|(_, total)| total)
+                    .sum();
+                (mov, total as f64 / self.playouts as f64)
+            })
+            .collect()
+    }
+
+    pub fn average_score(&self, grid: &Grid, mov: Move, seed: u64) -> f64 {
+        let total: u64 = self
+            .chunks(seed)
+            .into_iter()
+            .map(|(seed, playouts)| self.play_chunk(grid, mov, seed, playouts))
+            .sum();
+        total as f64 / self.playouts as f64
+    }
+
+    // The seed and number of playouts of every chunk, derived from the seed of the move
+    fn chunks(&self, seed: u64) -> Vec<(u64, usize)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..self.playouts)
+            .step_by(PLAYOUT_CHUNK)
+            .map(|start| (rng.gen(), PLAYOUT_CHUNK.min(self.playouts - start)))
+            .collect()
+    }
+
+    // Total final score of the playouts of one chunk
+    fn play_chunk(&self, grid: &Grid, mov: Move, seed: u64, playouts: usize) -> u64 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..playouts)
+            .map(|_| self.playout(grid, mov, &mut rng))
+            .sum()
+    }
+
+    fn playout<R: Rng>(&self, grid: &Grid, first_move: Move, rng: &mut R) -> u64 {
+        let mut grid = grid.clone();
+        let mut status = grid.attempt_with_rng(first_move, rng);
+        let mut moves = 1;
+        while status != GameStatus::Lost && self.max_rollout_moves.is_none_or(|max| moves < max) {
+            let mov = match self.policy.choose(&grid, rng) {
+                Some(mov) => mov,
+                None => break,
+            };
+            status = grid.attempt_with_rng(mov, rng);
+            moves += 1;
+        }
+        grid.get_score()
+    }
+}
+
+// The move with the highest average, the first one on a tie
+fn best_of(evaluations: Vec<(Move, f64)>) -> Option<Move> {
+    evaluations
+        .into_iter()
+        .fold(None, |best: Option<(Move, f64)>, (mov, value)| match best {
+            Some((_, best_value)) if best_value >= value => best,
+            _ => Some((mov, value)),
+        })
+        .map(|(mov, _)| mov)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MonteCarlo, RolloutPolicy};
+    use crate::grid::{Grid, Move, Rules, Topology};
+
+    #[test]
+    fn same_seed_gives_same_analysis() {
+        let row1 = [2, 4, 0, 0];
+        let row2 = [0, 8, 0, 0];
+        let row3 = [0, 0, 2, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let first = MonteCarlo::new(3).with_playouts(20).analyze(&grid);
+        let second = MonteCarlo::new(3).with_playouts(20).analyze(&grid);
+        assert_eq!(first, second);
+        assert_eq!(first.len(), grid.get_valid_moves().len());
+    }
+
+    #[test]
+    fn prefers_the_move_that_survives() {
+        // Moving left leaves no merges whatever spawns, so it ends the game right away
+        let row1 = [2, 4, 8, 16];
+        let row2 = [4, 8, 16, 32];
+        let row3 = [8, 16, 32, 64];
+        let row4 = [0, 2, 128, 256];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let mut bot = MonteCarlo::new(1)
+            .with_playouts(10)
+            .with_policy(RolloutPolicy::Greedy);
+        assert_eq!(bot.best_move(&grid), Some(Move::Down));
+    }
+
+    #[test]
+    fn rollouts_can_be_cut_short() {
+        let grid = Grid::new_random();
+        let bot = MonteCarlo::new(5).with_playouts(5).with_max_rollout_moves(1);
+        let mov = grid.get_valid_moves()[0];
+
+        // A single move can score at most one merge per pair of tiles
+        assert!(bot.average_score(&grid, mov, 9) <= grid.get_score() as f64 + 8.0);
+    }
+
+    #[test]
+    fn works_with_other_rules() {
+        let rules = Rules {
+            topology: Topology::Torus,
+            diagonal_moves: true,
+        };
+        let grid = Grid::new_random_with_rules(rules);
+        let mut bot = MonteCarlo::new(2).with_playouts(3).with_max_rollout_moves(50);
+        assert!(bot.best_move(&grid).is_some());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_playouts_match_single_thread() {
+        let grid = Grid::new_random();
+        // 25 playouts make two full chunks and a short one for every move
+        let serial = MonteCarlo::new(4).with_playouts(25).analyze(&grid);
+        for threads in [1, 4, 16] {
+            let parallel = MonteCarlo::new(4)
+                .with_playouts(25)
+                .with_threads(threads)
+                .analyze_parallel(&grid);
+            assert_eq!(serial, parallel);
+        }
+    }
+}
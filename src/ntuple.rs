@@ -0,0 +1,432 @@
+use std::fmt;
+use std::io::{Read, Write};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::bitboard::Bitboard;
+use crate::grid::{Grid, Move, Rules, MOVES};
+
+// Cells are numbered 4 * row + column
+const DEFAULT_PATTERNS: [&[usize]; 5] = [
+    &[0, 1, 2, 3],
+    &[4, 5, 6, 7],
+    &[0, 1, 4, 5],
+    &[1, 2, 5, 6],
+    &[5, 6, 9, 10],
+];
+// Every cell adds 4 bits to the index of a lookup table, so longer tuples get big fast
+const MAX_TUPLE_LENGTH: usize = 6;
+const DEFAULT_LEARNING_RATE: f32 = 0.1;
+const MAGIC: &[u8; 4] = b"NTUP";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(std::io::Error),
+    InvalidFormat(String),
+}
+
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsError::Io(error) => write!(f, "couldn't read or write weights: {}", error),
+            WeightsError::InvalidFormat(reason) => write!(f, "invalid weight file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+impl From<std::io::Error> for WeightsError {
+    fn from(error: std::io::Error) -> Self {
+        WeightsError::Io(error)
+    }
+}
+
+// One pattern of cells and its lookup table. The table is shared by the 8 rotations and
+// reflections of the pattern, since they are equally good positions.
+#[derive(Clone, Debug, PartialEq)]
+struct Tuple {
+    cells: Vec<usize>,
+    symmetries: Vec<Vec<usize>>,
+    weights: Vec<f32>,
+}
+
+impl Tuple {
+    fn new(cells: &[usize]) -> Self {
+        Self {
+            cells: cells.to_vec(),
+            symmetries: symmetries(cells),
+            weights: vec![0.0; 1 << (4 * cells.len())],
+        }
+    }
+
+    fn indices(&self, board: Bitboard) -> impl Iterator<Item = usize> + '_ {
+        self.symmetries.iter().map(move |cells| {
+            cells.iter().enumerate().fold(0, |index, (i, cell)| {
+                index | (board.get_exponent(cell / 4, cell % 4) as usize) << (4 * i)
+            })
+        })
+    }
+}
+
+// The pattern under every rotation and reflection of the board
+fn symmetries(cells: &[usize]) -> Vec<Vec<usize>> {
+    let rotate = |cell: usize| 4 * (cell % 4) + 3 - cell / 4;
+    let mirror = |cell: usize| 4 * (cell / 4) + 3 - cell % 4;
+    let mut pattern = cells.to_vec();
+    let mut all = Vec::new();
+    for _ in 0..4 {
+        all.push(pattern.clone());
+        all.push(pattern.iter().map(|cell| mirror(*cell)).collect());
+        pattern = pattern.iter().map(|cell| rotate(*cell)).collect();
+    }
+    all
+}
+
+// Values afterstates with a sum of lookup tables, one per tuple of cells. It only knows
+// the classic rules, as it plays on a `Bitboard`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NTupleNetwork {
+    tuples: Vec<Tuple>,
+}
+
+impl NTupleNetwork {
+    // Panics if a pattern is empty, longer than 6 cells or has a cell outside the board
+    pub fn new(patterns: &[&[usize]]) -> Self {
+        for pattern in patterns {
+            assert!(
+                valid_pattern(pattern),
+                "invalid tuple pattern {:?}",
+                pattern
+            );
+        }
+        Self {
+            tuples: patterns.iter().map(|pattern| Tuple::new(pattern)).collect(),
+        }
+    }
+
+    pub fn get_patterns(&self) -> Vec<&[usize]> {
+        self.tuples
+            .iter()
+            .map(|tuple| tuple.cells.as_slice())
+            .collect()
+    }
+
+    pub fn value(&self, board: Bitboard) -> f32 {
+        self.tuples
+            .iter()
+            .map(|tuple| {
+                tuple
+                    .indices(board)
+                    .map(|index| tuple.weights[index])
+                    .sum::<f32>()
+            })
+            .sum()
+    }
+
+    // Spreads the change evenly over every weight that contributes to the value
+    fn update(&mut self, board: Bitboard, change: f32) {
+        let lookups: usize = self.tuples.iter().map(|tuple| tuple.symmetries.len()).sum();
+        let change = change / lookups as f32;
+        for tuple in self.tuples.iter_mut() {
+            let indices: Vec<usize> = tuple.indices(board).collect();
+            for index in indices {
+                tuple.weights[index] += change;
+            }
+        }
+    }
+
+    // The reward plus the value of the afterstate of every valid move
+    pub fn evaluate_moves(&self, board: Bitboard) -> Vec<(Move, f32)> {
+        MOVES
+            .iter()
+            .filter_map(|mov| {
+                let (after, reward) = board.after_move(*mov)?;
+                Some((*mov, reward as f32 + self.value(after)))
+            })
+            .collect()
+    }
+
+    // None for grids with other rules than the classic ones, which the network can't play
+    pub fn best_move(&self, grid: &Grid) -> Option<Move> {
+        if grid.get_rules() != Rules::default() {
+            return None;
+        }
+        self.best_afterstate(Bitboard::try_from_grid(grid)?)
+            .map(|(mov, _, _)| mov)
+    }
+
+    fn best_afterstate(&self, board: Bitboard) -> Option<(Move, Bitboard, u32)> {
+        let mut best: Option<(Move, Bitboard, u32, f32)> = None;
+        for mov in MOVES {
+            if let Some((after, reward)) = board.after_move(mov) {
+                let value = reward as f32 + self.value(after);
+                if best.is_none_or(|(_, _, _, best_value)| value > best_value) {
+                    best = Some((mov, after, reward, value));
+                }
+            }
+        }
+        best.map(|(mov, after, reward, _)| (mov, after, reward))
+    }
+
+    // Binary format, all numbers little endian: "NTUP", the format version and the number
+    // of tuples as u32, then for every tuple its length as u32, its cells as one byte
+    // each and its lookup table as f32s.
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), WeightsError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.tuples.len() as u32).to_le_bytes())?;
+        for tuple in self.tuples.iter() {
+            writer.write_all(&(tuple.cells.len() as u32).to_le_bytes())?;
+            let cells: Vec<u8> = tuple.cells.iter().map(|cell| *cell as u8).collect();
+            writer.write_all(&cells)?;
+            let weights: Vec<u8> = tuple
+                .weights
+                .iter()
+                .flat_map(|weight| weight.to_le_bytes())
+                .collect();
+            writer.write_all(&weights)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Self, WeightsError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(WeightsError::InvalidFormat(
+                "not an n-tuple weight file".to_string(),
+            ));
+        }
+        let version = read_u32(reader)?;
+        if version != FORMAT_VERSION {
+            return Err(WeightsError::InvalidFormat(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let count = read_u32(reader)?;
+        let mut tuples = Vec::new();
+        for _ in 0..count {
+            let length = read_u32(reader)? as usize;
+            if length == 0 || length > MAX_TUPLE_LENGTH {
+                return Err(WeightsError::InvalidFormat(format!(
+                    "tuple of length {}",
+                    length
+                )));
+            }
+            let mut cells = vec![0; length];
+            reader.read_exact(&mut cells)?;
+            let cells: Vec<usize> = cells.into_iter().map(|cell| cell as usize).collect();
+            if !valid_pattern(&cells) {
+                return Err(WeightsError::InvalidFormat(format!(
+                    "invalid tuple {:?}",
+                    cells
+                )));
+            }
+            let mut tuple = Tuple::new(&cells);
+            let mut bytes = vec![0; 4 * tuple.weights.len()];
+            reader.read_exact(&mut bytes)?;
+            for (weight, chunk) in tuple.weights.iter_mut().zip(bytes.chunks_exact(4)) {
+                *weight = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            }
+            tuples.push(tuple);
+        }
+        Ok(Self { tuples })
+    }
+
+    pub fn save_to_file(&self, path: &str) -> Result<(), WeightsError> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.save(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> Result<Self, WeightsError> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::load(&mut reader)
+    }
+}
+
+impl Default for NTupleNetwork {
+    fn default() -> Self {
+        Self::new(&DEFAULT_PATTERNS)
+    }
+}
+
+fn valid_pattern(cells: &[usize]) -> bool {
+    !cells.is_empty() && cells.len() <= MAX_TUPLE_LENGTH && cells.iter().all(|cell| *cell < 16)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, WeightsError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrainingStats {
+    pub games: usize,
+    pub average_score: f64,
+    pub best_score: u64,
+    // Largest tile reached in any game
+    pub max_tile: u64,
+}
+
+// Trains a network by self play with TD(0) on afterstates: after every move the value of
+// the previous afterstate is moved towards the reward of the move plus the value of the
+// new afterstate.
+pub struct Trainer {
+    network: NTupleNetwork,
+    learning_rate: f32,
+    rng: StdRng,
+}
+
+impl Trainer {
+    pub fn new(network: NTupleNetwork, seed: u64) -> Self {
+        Self {
+            network,
+            learning_rate: DEFAULT_LEARNING_RATE,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn with_learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn get_network(&self) -> &NTupleNetwork {
+        &self.network
+    }
+
+    pub fn into_network(self) -> NTupleNetwork {
+        self.network
+    }
+
+    pub fn train(&mut self, games: usize) -> TrainingStats {
+        let mut stats = TrainingStats {
+            games,
+            ..TrainingStats::default()
+        };
+        let mut total = 0;
+        for _ in 0..games {
+            let (score, board) = self.play_episode();
+            total += score;
+            stats.best_score = stats.best_score.max(score);
+            stats.max_tile = stats.max_tile.max(1 << board.max_exponent());
+        }
+        if games > 0 {
+            stats.average_score = total as f64 / games as f64;
+        }
+        stats
+    }
+
+    // Plays one game, learning after every move. Returns the score and the final board.
+    pub fn play_episode(&mut self) -> (u64, Bitboard) {
+        let mut board = Bitboard::default()
+            .spawn(&mut self.rng)
+            .spawn(&mut self.rng);
+        let mut previous: Option<Bitboard> = None;
+        let mut score = 0;
+        while let Some((_, after, reward)) = self.network.best_afterstate(board) {
+            if let Some(previous) = previous {
+                let target = reward as f32 + self.network.value(after);
+                let error = target - self.network.value(previous);
+                self.network.update(previous, self.learning_rate * error);
+            }
+            previous = Some(after);
+            score += reward as u64;
+            board = after.spawn(&mut self.rng);
+        }
+        // Nothing follows the last afterstate, so its value should be zero
+        if let Some(previous) = previous {
+            let error = -self.network.value(previous);
+            self.network.update(previous, self.learning_rate * error);
+        }
+        (score, board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{symmetries, NTupleNetwork, Trainer, WeightsError};
+    use crate::bitboard::Bitboard;
+    use crate::grid::Grid;
+
+    #[test]
+    fn symmetries_cover_the_corners() {
+        let corners: Vec<usize> = symmetries(&[0]).into_iter().flatten().collect();
+        for corner in [0, 3, 12, 15] {
+            assert_eq!(corners.iter().filter(|cell| **cell == corner).count(), 2);
+        }
+    }
+
+    #[test]
+    fn symmetric_boards_have_the_same_value() {
+        let mut network = Trainer::new(NTupleNetwork::default(), 1);
+        network.train(5);
+        let network = network.into_network();
+
+        let row1 = [2, 4, 8, 0];
+        let row2 = [0, 2, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 16];
+        let mirrored = [row1, row2, row3, row4].map(|mut row| {
+            row.reverse();
+            row
+        });
+        let value = network.value(Bitboard::from_cells(&[row1, row2, row3, row4]));
+        assert!((value - network.value(Bitboard::from_cells(&mirrored))).abs() < 1e-3);
+    }
+
+    #[test]
+    fn same_seed_trains_the_same_network() {
+        let mut first = Trainer::new(NTupleNetwork::default(), 7);
+        let mut second = Trainer::new(NTupleNetwork::default(), 7);
+        assert_eq!(first.train(10), second.train(10));
+        assert_eq!(first.get_network(), second.get_network());
+    }
+
+    #[test]
+    fn weights_survive_a_round_trip() {
+        let mut trainer = Trainer::new(NTupleNetwork::new(&[&[0, 1, 2], &[5, 9]]), 3);
+        trainer.train(3);
+        let network = trainer.into_network();
+
+        let mut bytes = Vec::new();
+        network.save(&mut bytes).unwrap();
+        let loaded = NTupleNetwork::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, network);
+        assert_eq!(loaded.get_patterns(), vec![&[0, 1, 2][..], &[5, 9][..]]);
+    }
+
+    #[test]
+    fn bad_weight_files_are_rejected() {
+        let magic = NTupleNetwork::load(&mut &b"TUPN\x01\0\0\0\0\0\0\0"[..]);
+        assert!(matches!(magic, Err(WeightsError::InvalidFormat(_))));
+
+        let cell = NTupleNetwork::load(&mut &b"NTUP\x01\0\0\0\x01\0\0\0\x01\0\0\0\x10"[..]);
+        assert!(matches!(cell, Err(WeightsError::InvalidFormat(_))));
+
+        let truncated = NTupleNetwork::load(&mut &b"NTUP\x01\0\0\0\x01\0\0\0\x01\0\0\0\x03"[..]);
+        assert!(matches!(truncated, Err(WeightsError::Io(_))));
+    }
+
+    #[test]
+    fn trained_network_picks_valid_moves() {
+        let mut trainer = Trainer::new(NTupleNetwork::default(), 11);
+        let stats = trainer.train(20);
+        assert_eq!(stats.games, 20);
+        assert!(stats.average_score > 0.0);
+        assert!(stats.max_tile >= 64);
+
+        let grid = Grid::new_random();
+        let mov = trainer.get_network().best_move(&grid).unwrap();
+        assert!(grid.get_valid_moves().contains(&mov));
+
+        let wrap = Grid::new_with_rules(grid.cells, "wrap".parse().unwrap());
+        assert_eq!(trainer.get_network().best_move(&wrap), None);
+    }
+}
@@ -0,0 +1,88 @@
+// Tile colours, shared by the web app and the terminal UI so both look the same
+
+// Background of the board around the tiles
+pub const BOARD_COLOR: &str = "#ccc";
+
+pub fn get_color_for_cell(value: u64) -> &'static str {
+    match value {
+        0 => "rgba(238, 228, 218, 0.35)",
+        2 => "#eee4da",
+        4 => "#ede0c8",
+        8 => "#f2b179",
+        16 => "#f59563",
+        32 => "#f67c5f",
+        64 => "#f65e3b",
+        128 => "#edcf72",
+        256 => "#edcc61",
+        512 => "#edc850",
+        1024 => "#edc53f",
+        2048 => "#edc22e",
+        _ => "#3c3a32",
+    }
+}
+
+pub fn get_color_for_text(value: u64) -> &'static str {
+    match value {
+        2 => "#6c6462",
+        4 => "#6c6462",
+        _ => "#FFFFFF",
+    }
+}
+
+// Red, green and blue of a colour written as "#rgb", "#rrggbb" or "rgba(r, g, b, a)".
+// Transparent colours are blended over `background`.
+pub fn to_rgb(color: &str, background: (u8, u8, u8)) -> Option<(u8, u8, u8)> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<u8> = match hex.len() {
+            3 => hex
+                .chars()
+                .map(|digit| u8::from_str_radix(&digit.to_string(), 16).map(|value| value * 17))
+                .collect::<Result<_, _>>()
+                .ok()?,
+            6 => (0..3)
+                .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+                .collect::<Result<_, _>>()
+                .ok()?,
+            _ => return None,
+        };
+        return Some((digits[0], digits[1], digits[2]));
+    }
+    let inner = color.strip_prefix("rgba(")?.strip_suffix(')')?;
+    let parts: Vec<f32> = inner
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let [red, green, blue, alpha] = parts[..] else {
+        return None;
+    };
+    let blend =
+        |channel: f32, under: u8| (channel * alpha + under as f32 * (1.0 - alpha)).round() as u8;
+    Some((
+        blend(red, background.0),
+        blend(green, background.1),
+        blend(blue, background.2),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_color_for_cell, get_color_for_text, to_rgb, BOARD_COLOR};
+
+    #[test]
+    fn every_colour_converts() {
+        let background = to_rgb(BOARD_COLOR, (0, 0, 0)).unwrap();
+        assert_eq!(background, (204, 204, 204));
+        for exponent in 0..18 {
+            let value = if exponent == 0 { 0 } else { 1 << exponent };
+            assert!(to_rgb(get_color_for_cell(value), background).is_some());
+            assert!(to_rgb(get_color_for_text(value), background).is_some());
+        }
+        assert_eq!(to_rgb("#f65e3b", background), Some((246, 94, 59)));
+        assert_eq!(
+            to_rgb("rgba(0, 0, 0, 0.5)", (200, 100, 0)),
+            Some((100, 50, 0))
+        );
+        assert_eq!(to_rgb("red", background), None);
+    }
+}
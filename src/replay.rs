@@ -0,0 +1,186 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{Grid, Move, Rules};
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    // The step at this index can't be played on the position before it
+    InvalidMove(usize),
+    InvalidSpawn(usize),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "couldn't read or write replay: {}", error),
+            ReplayError::Json(error) => write!(f, "invalid replay: {}", error),
+            ReplayError::InvalidMove(step) => write!(f, "move {} isn't valid", step + 1),
+            ReplayError::InvalidSpawn(step) => {
+                write!(f, "spawn after move {} lands on a tile", step + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    #[serde(rename = "move")]
+    pub mov: Move,
+    // Position and value of the tile that spawned after the move
+    pub spawn: Option<(usize, usize, u64)>,
+}
+
+// Everything needed to play a game again: the rules, the starting tiles, and every move
+// with the tile that spawned after it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub rules: Rules,
+    pub start: [[u64; 4]; 4],
+    pub steps: Vec<Step>,
+}
+
+impl Replay {
+    pub fn new(grid: &Grid) -> Self {
+        Self {
+            rules: grid.get_rules(),
+            start: grid.cells,
+            steps: Vec::new(),
+        }
+    }
+
+    // Records a move after it was played successfully on `grid`
+    pub fn record(&mut self, mov: Move, grid: &Grid) {
+        self.steps.push(Step {
+            mov,
+            spawn: grid.get_last_spawn(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    // The starting position followed by the position after every step
+    pub fn positions(&self) -> Result<Vec<Grid>, ReplayError> {
+        let mut grid = Grid::new_with_rules(self.start, self.rules);
+        let mut positions = vec![grid.clone()];
+        for (i, step) in self.steps.iter().enumerate() {
+            grid = grid
+                .after_move(step.mov)
+                .ok_or(ReplayError::InvalidMove(i))?;
+            if let Some((x, y, value)) = step.spawn {
+                if x >= 4 || y >= 4 || !grid.insert_cell(x, y, value) {
+                    return Err(ReplayError::InvalidSpawn(i));
+                }
+            }
+            positions.push(grid.clone());
+        }
+        Ok(positions)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        serde_json::from_str(json).map_err(ReplayError::Json)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ReplayError> {
+        std::fs::write(path, self.to_json()).map_err(ReplayError::Io)
+    }
+
+    pub fn load(path: &str) -> Result<Self, ReplayError> {
+        let json = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, ReplayError, Step};
+    use crate::grid::{GameStatus, Grid, Move, Rules, Topology};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn play(rules: Rules) -> (Replay, Grid) {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut grid = Grid::new_random_with_rng(rules, &mut rng);
+        let mut replay = Replay::new(&grid);
+        for mov in [
+            Move::Left,
+            Move::Down,
+            Move::Right,
+            Move::Down,
+            Move::Left,
+            Move::Up,
+        ] {
+            if grid.attempt_with_rng(mov, &mut rng) != GameStatus::InvalidMove {
+                replay.record(mov, &grid);
+            }
+        }
+        (replay, grid)
+    }
+
+    #[test]
+    fn positions_replay_the_game() {
+        let rules = Rules {
+            topology: Topology::Torus,
+            diagonal_moves: false,
+        };
+        let (replay, grid) = play(rules);
+        let positions = replay.positions().unwrap();
+        assert_eq!(positions.len(), replay.len() + 1);
+
+        let last = positions.last().unwrap();
+        assert_eq!(last.cells, grid.cells);
+        assert_eq!(last.get_score(), grid.get_score());
+        assert_eq!(last.get_rules(), rules);
+    }
+
+    #[test]
+    fn replays_survive_json() {
+        let (replay, _) = play(Rules::default());
+        let json = replay.to_json();
+        assert!(json.contains("\"rules\":\"classic\""));
+        assert!(json.contains("\"move\":\"L\""));
+        assert_eq!(Replay::from_json(&json).unwrap(), replay);
+    }
+
+    #[test]
+    fn broken_replays_are_rejected() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let mut replay = Replay::new(&Grid::new([row1, row2, row3, row4]));
+        replay.steps.push(Step {
+            mov: Move::Left,
+            spawn: None,
+        });
+        assert!(matches!(
+            replay.positions(),
+            Err(ReplayError::InvalidMove(0))
+        ));
+
+        replay.steps[0] = Step {
+            mov: Move::Right,
+            spawn: Some((0, 3, 2)),
+        };
+        assert!(matches!(
+            replay.positions(),
+            Err(ReplayError::InvalidSpawn(0))
+        ));
+
+        assert!(Replay::from_json("{\"rules\":\"hex\"}").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::grid::{Grid, Move};
+use crate::heuristic::{Heuristic, WeightedHeuristic};
+use crate::replay::{Replay, ReplayError};
+use crate::solver::Expectimax;
+
+// Moves losing more expected value than this, in the units of the heuristic, are blunders
+const DEFAULT_BLUNDER_THRESHOLD: f64 = 500.0;
+const DEFAULT_DEPTH: usize = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveReview {
+    pub played: Move,
+    // Expected value of every valid move
+    pub evaluations: Vec<(Move, f64)>,
+    pub best_move: Option<Move>,
+    // Expected value lost by playing this move instead of the best one
+    pub cost: f64,
+    pub is_blunder: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Review {
+    pub moves: Vec<MoveReview>,
+}
+
+impl Review {
+    pub fn total_cost(&self) -> f64 {
+        self.moves.iter().map(|review| review.cost).sum()
+    }
+
+    // Indices of the moves that were blunders
+    pub fn get_blunders(&self) -> Vec<usize> {
+        (0..self.moves.len())
+            .filter(|i| self.moves[*i].is_blunder)
+            .collect()
+    }
+}
+
+// Compares every move of a game with the moves the expectimax solver prefers
+pub struct Reviewer<H: Heuristic> {
+    solver: Expectimax<H>,
+    blunder_threshold: f64,
+}
+
+impl<H: Heuristic> Reviewer<H> {
+    pub fn new(solver: Expectimax<H>) -> Self {
+        Self {
+            solver,
+            blunder_threshold: DEFAULT_BLUNDER_THRESHOLD,
+        }
+    }
+
+    pub fn with_blunder_threshold(mut self, threshold: f64) -> Self {
+        self.blunder_threshold = threshold;
+        self
+    }
+
+    // Reviews the move played from `position`. Moves that aren't valid cost nothing.
+    pub fn review_move(&self, position: &Grid, played: Move) -> MoveReview {
+        let analysis = self.solver.analyze(position);
+        let value = |mov: Move| {
+            analysis
+                .evaluations
+                .iter()
+                .find(|(evaluated, _)| *evaluated == mov)
+                .map(|(_, value)| *value)
+        };
+        let cost = match (analysis.best_move.and_then(value), value(played)) {
+            (Some(best), Some(played)) => (best - played).max(0.0),
+            _ => 0.0,
+        };
+        MoveReview {
+            played,
+            best_move: analysis.best_move,
+            evaluations: analysis.evaluations,
+            cost,
+            is_blunder: cost > self.blunder_threshold,
+        }
+    }
+
+    pub fn review(&self, replay: &Replay) -> Result<Review, ReplayError> {
+        let positions = replay.positions()?;
+        let moves = replay
+            .steps
+            .iter()
+            .zip(positions.iter())
+            .map(|(step, position)| self.review_move(position, step.mov))
+            .collect();
+        Ok(Review { moves })
+    }
+}
+
+impl Default for Reviewer<WeightedHeuristic> {
+    fn default() -> Self {
+        Self::new(Expectimax::default().with_depth(DEFAULT_DEPTH))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reviewer;
+    use crate::grid::{Grid, Move};
+    use crate::replay::{Replay, Step};
+
+    #[test]
+    fn the_losing_move_is_a_blunder() {
+        // Moving left leaves no merges whatever spawns, moving down always leaves one
+        let row1 = [2, 4, 8, 16];
+        let row2 = [4, 8, 16, 32];
+        let row3 = [8, 16, 32, 64];
+        let row4 = [0, 2, 128, 256];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let reviewer = Reviewer::default();
+        let left = reviewer.review_move(&grid, Move::Left);
+        assert_eq!(left.best_move, Some(Move::Down));
+        assert!(left.is_blunder);
+
+        let down = reviewer.review_move(&grid, Move::Down);
+        assert_eq!(down.cost, 0.0);
+        assert!(!down.is_blunder);
+    }
+
+    #[test]
+    fn reviews_add_up_the_cost_of_mistakes() {
+        let row1 = [2, 4, 8, 16];
+        let row2 = [4, 8, 16, 32];
+        let row3 = [8, 16, 32, 64];
+        let row4 = [0, 2, 128, 256];
+        let mut replay = Replay::new(&Grid::new([row1, row2, row3, row4]));
+        replay.steps.push(Step {
+            mov: Move::Left,
+            spawn: Some((3, 3, 2)),
+        });
+
+        let review = Reviewer::default()
+            .with_blunder_threshold(f64::INFINITY)
+            .review(&replay)
+            .unwrap();
+        assert_eq!(review.moves.len(), 1);
+        assert!(review.total_cost() > 0.0);
+        assert!(review.get_blunders().is_empty());
+    }
+}
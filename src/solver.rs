@@ -0,0 +1,514 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::grid::{Grid, Move, CHANCE_FOR_TWO};
+use crate::heuristic::{Heuristic, WeightedHeuristic};
+
+const DEFAULT_DEPTH: usize = 3;
+// Spawns less likely than this are left out of chance nodes
+const DEFAULT_MIN_SPAWN_PROBABILITY: f64 = 0.01;
+const DEFAULT_LOSS_PENALTY: f64 = 100_000.0;
+const TABLE_SHARDS: usize = 64;
+// About 50 MB of positions, a shard is emptied when it fills up
+const MAX_TABLE_ENTRIES: usize = 1 << 20;
+// A parallel search splits the tree until there are this many subtrees per thread, so
+// the threads stay busy even when one subtree is much bigger than the others
+#[cfg(feature = "parallel")]
+const TASKS_PER_THREAD: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub struct Analysis {
+    pub best_move: Option<Move>,
+    // Expected value of every valid move, in the order of `Grid::get_valid_moves`
+    pub evaluations: Vec<(Move, f64)>,
+    // Deepest search that finished within the time budget
+    pub depth: usize,
+}
+
+type TableKey = ([[u64; 4]; 4], u64, usize);
+
+// Values of the positions already searched, keyed by tiles, score and remaining depth,
+// which is everything a `Heuristic` may look at. It's split into shards with their
+// own lock, so the threads of a parallel search rarely wait for each other. Every value is
+// the same whichever thread stores it first, so sharing it doesn't change the results.
+struct TranspositionTable {
+    shards: Vec<Mutex<HashMap<TableKey, f64>>>,
+}
+
+impl TranspositionTable {
+    fn new() -> Self {
+        Self {
+            shards: (0..TABLE_SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn shard(&self, key: &TableKey) -> &Mutex<HashMap<TableKey, f64>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % TABLE_SHARDS]
+    }
+
+    fn get(&self, key: &TableKey) -> Option<f64> {
+        self.shard(key).lock().unwrap().get(key).copied()
+    }
+
+    fn insert(&self, key: TableKey, value: f64) {
+        let mut shard = self.shard(&key).lock().unwrap();
+        if shard.len() >= MAX_TABLE_ENTRIES / TABLE_SHARDS {
+            shard.clear();
+        }
+        shard.insert(key, value);
+    }
+}
+
+// Expectimax search over player moves and tile spawns. The search deepens one move at a
+// time up to the maximum depth, and stops early when the time budget runs out, keeping
+// the result of the deepest search that finished.
+pub struct Expectimax<H: Heuristic> {
+    heuristic: H,
+    max_depth: usize,
+    time_budget_ms: Option<f64>,
+    min_spawn_probability: f64,
+    loss_penalty: f64,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+}
+
+impl<H: Heuristic> Expectimax<H> {
+    pub fn new(heuristic: H) -> Self {
+        Self {
+            heuristic,
+            max_depth: DEFAULT_DEPTH,
+            time_budget_ms: None,
+            min_spawn_probability: DEFAULT_MIN_SPAWN_PROBABILITY,
+            loss_penalty: DEFAULT_LOSS_PENALTY,
+            #[cfg(feature = "parallel")]
+            threads: crate::parallel::default_threads(),
+        }
+    }
+
+    pub fn with_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth.max(1);
+        self
+    }
+
+    pub fn with_time_budget(mut self, milliseconds: f64) -> Self {
+        self.time_budget_ms = Some(milliseconds);
+        self
+    }
+
+    pub fn with_min_spawn_probability(mut self, probability: f64) -> Self {
+        self.min_spawn_probability = probability;
+        self
+    }
+
+    pub fn with_loss_penalty(mut self, penalty: f64) -> Self {
+        self.loss_penalty = penalty;
+        self
+    }
+
+    pub fn best_move(&self, grid: &Grid) -> Option<Move> {
+        self.analyze(grid).best_move
+    }
+
+    pub fn analyze(&self, grid: &Grid) -> Analysis {
+        self.deepen(|depth, deadline, table| self.evaluate_moves(grid, depth, deadline, table))
+    }
+
+    fn deepen(
+        &self,
+        evaluate_moves: impl Fn(usize, Option<f64>, &TranspositionTable) -> Option<Vec<(Move, f64)>>,
+    ) -> Analysis {
+        let deadline = self.time_budget_ms.map(|budget| now_ms() + budget);
+        let table = TranspositionTable::new();
+        let mut analysis = Analysis {
+            best_move: None,
+            evaluations: Vec::new(),
+            depth: 0,
+        };
+        for depth in 1..=self.max_depth {
+            match evaluate_moves(depth, deadline, &table) {
+                Some(evaluations) => {
+                    analysis = Analysis {
+                        best_move: best_of(&evaluations),
+                        evaluations,
+                        depth,
+                    }
+                }
+                None => break,
+            }
+        }
+        analysis
+    }
+
+    // Returns None if the deadline passed before the search finished
+    fn evaluate_moves(
+        &self,
+        grid: &Grid,
+        depth: usize,
+        deadline: Option<f64>,
+        table: &TranspositionTable,
+    ) -> Option<Vec<(Move, f64)>> {
+        let mut evaluations = Vec::new();
+        for mov in grid.get_valid_moves() {
+            // Valid moves always have an after state
+            let after = grid.after_move(mov).unwrap();
+            evaluations.push((mov, self.chance_value(&after, depth, deadline, table)?));
+        }
+        Some(evaluations)
+    }
+
+    fn max_value(
+        &self,
+        grid: &Grid,
+        depth: usize,
+        deadline: Option<f64>,
+        table: &TranspositionTable,
+    ) -> Option<f64> {
+        if depth == 0 {
+            return Some(self.heuristic.evaluate(grid));
+        }
+        if deadline.is_some_and(|deadline| now_ms() > deadline) {
+            return None;
+        }
+        let key = (grid.cells, grid.get_score(), depth);
+        if let Some(value) = table.get(&key) {
+            return Some(value);
+        }
+        let evaluations = self.evaluate_moves(grid, depth, deadline, table)?;
+        let value = match evaluations.iter().map(|(_, value)| *value).reduce(f64::max) {
+            Some(value) => value,
+            None => self.heuristic.evaluate(grid) - self.loss_penalty,
+        };
+        table.insert(key, value);
+        Some(value)
+    }
+
+    // Average over every spawn that is likely enough, weighted by its probability
+    fn chance_value(
+        &self,
+        after: &Grid,
+        depth: usize,
+        deadline: Option<f64>,
+        table: &TranspositionTable,
+    ) -> Option<f64> {
+        let mut values = Vec::new();
+        for (probability, child) in self.spawns(after) {
+            values.push((
+                probability,
+                self.max_value(&child, depth - 1, deadline, table)?,
+            ));
+        }
+        Some(weighted_average(&values))
+    }
+
+    // Every spawn that is likely enough, with its probability. When there's none, the
+    // after state itself is the only outcome.
+    fn spawns(&self, after: &Grid) -> Vec<(f64, Grid)> {
+        let empty_cells = Grid::get_empty_cells(after.cells);
+        let mut spawns = Vec::new();
+        if !empty_cells.is_empty() {
+            let per_cell = 1.0 / empty_cells.len() as f64;
+            for (value, chance) in [(2, CHANCE_FOR_TWO), (4, 1.0 - CHANCE_FOR_TWO)] {
+                let probability = chance * per_cell;
+                if probability < self.min_spawn_probability {
+                    continue;
+                }
+                for (x, y) in empty_cells.iter() {
+                    let mut child = after.clone();
+                    child.insert_cell(*x, *y, value);
+                    spawns.push((probability, child));
+                }
+            }
+        }
+        if spawns.is_empty() {
+            spawns.push((1.0, after.clone()));
+        }
+        spawns
+    }
+}
+
+// Part of the search tree that a parallel search expands before the threads start. Its
+// leaves are the subtrees handed to the threads.
+#[cfg(feature = "parallel")]
+enum Node {
+    // Index of a subtree in the list of subtrees
+    Leaf(usize),
+    // A position where the player moves. Every valid move has its spawns, with their
+    // probabilities.
+    Max {
+        grid: Box<Grid>,
+        depth: usize,
+        moves: Vec<Vec<(f64, Node)>>,
+    },
+}
+
+// The tree is expanded a few moves deep, until there are enough subtrees to keep every
+// thread busy, and the subtrees are searched on several threads sharing one transposition
+// table. The values are then added up in the same order as a search on a single thread,
+// so the results are exactly the same.
+#[cfg(feature = "parallel")]
+impl<H: Heuristic + Sync> Expectimax<H> {
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn analyze_parallel(&self, grid: &Grid) -> Analysis {
+        self.deepen(|depth, deadline, table| {
+            self.evaluate_moves_parallel(grid, depth, deadline, table)
+        })
+    }
+
+    fn evaluate_moves_parallel(
+        &self,
+        grid: &Grid,
+        depth: usize,
+        deadline: Option<f64>,
+        table: &TranspositionTable,
+    ) -> Option<Vec<(Move, f64)>> {
+        // One more level of the tree at a time, as long as there are too few subtrees
+        let mut levels = 0;
+        let (root, subtrees) = loop {
+            levels += 1;
+            let mut subtrees = Vec::new();
+            let root = self.expand(grid, depth, levels, &mut subtrees);
+            if subtrees.len() >= self.threads * TASKS_PER_THREAD || levels >= depth {
+                break (root, subtrees);
+            }
+        };
+        let values = crate::parallel::map(&subtrees, self.threads, |(child, depth)| {
+            self.max_value(child, *depth, deadline, table)
+        });
+        let values: Vec<f64> = values.into_iter().collect::<Option<_>>()?;
+
+        let Node::Max { grid, moves, .. } = root else {
+            unreachable!("the root is always expanded");
+        };
+        let evaluations = grid
+            .get_valid_moves()
+            .into_iter()
+            .zip(moves.iter())
+            .map(|(mov, spawns)| (mov, self.chance_value_of(spawns, &values, table)))
+            .collect();
+        Some(evaluations)
+    }
+
+    // Expands the position `levels` moves deep, adding the positions below to `subtrees`
+    fn expand(
+        &self,
+        grid: &Grid,
+        depth: usize,
+        levels: usize,
+        subtrees: &mut Vec<(Grid, usize)>,
+    ) -> Node {
+        if levels == 0 || depth == 0 {
+            subtrees.push((grid.clone(), depth));
+            return Node::Leaf(subtrees.len() - 1);
+        }
+        let moves = grid
+            .get_valid_moves()
+            .into_iter()
+            .map(|mov| {
+                // Valid moves always have an after state
+                let after = grid.after_move(mov).unwrap();
+                self.spawns(&after)
+                    .into_iter()
+                    .map(|(probability, child)| {
+                        (probability, self.expand(&child, depth - 1, levels - 1, subtrees))
+                    })
+                    .collect()
+            })
+            .collect();
+        Node::Max {
+            grid: Box::new(grid.clone()),
+            depth,
+            moves,
+        }
+    }
+
+    // The value of an expanded position, the same way `max_value` computes it
+    fn value_of(&self, node: &Node, values: &[f64], table: &TranspositionTable) -> f64 {
+        match node {
+            Node::Leaf(index) => values[*index],
+            Node::Max { grid, depth, moves } => {
+                let value = match moves
+                    .iter()
+                    .map(|spawns| self.chance_value_of(spawns, values, table))
+                    .reduce(f64::max)
+                {
+                    Some(value) => value,
+                    None => self.heuristic.evaluate(grid) - self.loss_penalty,
+                };
+                table.insert((grid.cells, grid.get_score(), *depth), value);
+                value
+            }
+        }
+    }
+
+    fn chance_value_of(
+        &self,
+        spawns: &[(f64, Node)],
+        values: &[f64],
+        table: &TranspositionTable,
+    ) -> f64 {
+        let weighted: Vec<(f64, f64)> = spawns
+            .iter()
+            .map(|(probability, child)| (*probability, self.value_of(child, values, table)))
+            .collect();
+        weighted_average(&weighted)
+    }
+}
+
+impl Default for Expectimax<WeightedHeuristic> {
+    fn default() -> Self {
+        Self::new(WeightedHeuristic::default())
+    }
+}
+
+fn weighted_average(values: &[(f64, f64)]) -> f64 {
+    let total: f64 = values
+        .iter()
+        .map(|(probability, value)| probability * value)
+        .sum();
+    let total_probability: f64 = values.iter().map(|(probability, _)| probability).sum();
+    total / total_probability
+}
+
+fn best_of(evaluations: &[(Move, f64)]) -> Option<Move> {
+    evaluations
+        .iter()
+        .fold(None, |best: Option<(Move, f64)>, (mov, value)| match best {
+            Some((_, best_value)) if best_value >= *value => best,
+            _ => Some((*mov, *value)),
+        })
+        .map(|(mov, _)| mov)
+}
+
+// Milliseconds from an arbitrary starting point. std::time::Instant isn't available in
+// the browser, so the JS clock is used there.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+// Measured on the monotonic clock, so adjusting the system clock doesn't move deadlines
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expectimax;
+    use crate::grid::{Grid, Move};
+    use crate::heuristic::{Feature, WeightedHeuristic};
+
+    #[test]
+    fn takes_the_obvious_merge() {
+        let row1 = [1024, 1024, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 2];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let best = Expectimax::default().with_depth(1).best_move(&grid);
+        assert!(best == Some(Move::Left) || best == Some(Move::Right));
+    }
+
+    #[test]
+    fn evaluates_every_valid_move() {
+        let row1 = [2, 4, 0, 0];
+        let row2 = [4, 2, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let analysis = Expectimax::default()
+            .with_depth(2)
+            .with_min_spawn_probability(0.0)
+            .analyze(&grid);
+        let moves: Vec<Move> = analysis.evaluations.iter().map(|(mov, _)| *mov).collect();
+        assert_eq!(moves, grid.get_valid_moves());
+        assert_eq!(analysis.depth, 2);
+    }
+
+    #[test]
+    fn avoids_the_move_that_loses() {
+        // Moving left leaves no merges whatever spawns, moving down always leaves one
+        let row1 = [2, 4, 8, 16];
+        let row2 = [4, 8, 16, 32];
+        let row3 = [8, 16, 32, 64];
+        let row4 = [0, 2, 128, 256];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let analysis = Expectimax::default()
+            .with_depth(2)
+            .with_loss_penalty(1e9)
+            .analyze(&grid);
+        assert_eq!(analysis.evaluations.len(), 2);
+        assert_eq!(analysis.best_move, Some(Move::Down));
+    }
+
+    #[test]
+    fn no_moves_on_a_lost_grid() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [2, 4, 2, 4];
+        let row4 = [4, 2, 4, 2];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let heuristic = WeightedHeuristic::new(vec![(Feature::EmptyCells, 1.0)]);
+        let analysis = Expectimax::new(heuristic).analyze(&grid);
+        assert_eq!(analysis.best_move, None);
+        assert!(analysis.evaluations.is_empty());
+    }
+
+    #[test]
+    fn zero_time_budget_still_returns_a_move() {
+        let grid = Grid::new_random();
+        let analysis = Expectimax::default()
+            .with_depth(6)
+            .with_time_budget(0.0)
+            .analyze(&grid);
+        assert!(analysis.best_move.is_some());
+        assert!(analysis.depth >= 1 && analysis.depth < 6);
+    }
+
+    #[test]
+    fn closures_are_heuristics() {
+        let row1 = [2, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        // Prefers a tile in the top right corner
+        let corner = |grid: &Grid| grid.cells[0][3] as f64;
+        let best = Expectimax::new(corner).with_depth(1).best_move(&grid);
+        assert_eq!(best, Some(Move::Right));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_search_matches_single_thread() {
+        let row1 = [2, 4, 8, 0];
+        let row2 = [0, 16, 2, 0];
+        let row3 = [0, 0, 4, 0];
+        let row4 = [2, 0, 0, 64];
+        let grid = Grid::new([row1, row2, row3, row4]);
+
+        let solver = Expectimax::default().with_depth(3).with_threads(4);
+        let parallel = solver.analyze_parallel(&grid);
+        assert_eq!(parallel, solver.analyze(&grid));
+        assert_eq!(parallel, solver.analyze_parallel(&grid));
+        // Enough threads that the tree is split two moves deep
+        let split = Expectimax::default().with_depth(3).with_threads(16);
+        assert_eq!(parallel, split.analyze_parallel(&grid));
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::grid::{slide_line, Move, CHANCE_FOR_TWO, MOVES};
+
+const MAGIC: &[u8; 4] = b"TB48";
+const FORMAT_VERSION: u32 = 1;
+// Cells are packed 4 bits each into a u64 key
+const MAX_CELLS: usize = 9;
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(std::io::Error),
+    InvalidFormat(String),
+}
+
+impl fmt::Display for TablebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TablebaseError::Io(error) => write!(f, "couldn't read or write tablebase: {}", error),
+            TablebaseError::InvalidFormat(reason) => {
+                write!(f, "invalid tablebase file: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+impl From<std::io::Error> for TablebaseError {
+    fn from(error: std::io::Error) -> Self {
+        TablebaseError::Io(error)
+    }
+}
+
+// A board smaller than the classic one, with the classic rules. Cells are stored row by
+// row as tile values, 0 for empty.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SmallBoard {
+    width: usize,
+    height: usize,
+    cells: Vec<u64>,
+}
+
+impl SmallBoard {
+    // Panics if the board has more than 9 cells or the cells don't fit the size
+    pub fn new(width: usize, height: usize, cells: Vec<u64>) -> Self {
+        assert!(
+            width * height > 0 && width * height <= MAX_CELLS,
+            "boards up to 9 cells are supported"
+        );
+        assert_eq!(cells.len(), width * height);
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn empty(width: usize, height: usize) -> Self {
+        Self::new(width, height, vec![0; width * height])
+    }
+
+    pub fn get_cells(&self) -> &[u64] {
+        &self.cells
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> u64 {
+        self.cells[x * self.width + y]
+    }
+
+    // Lines of cell indices along the move, each ending on the edge the tiles move towards
+    fn lines(&self, mov: Move) -> Vec<Vec<usize>> {
+        let (width, height) = (self.width, self.height);
+        match mov {
+            Move::Right => (0..height)
+                .map(|x| (0..width).map(|y| x * width + y).collect())
+                .collect(),
+            Move::Left => (0..height)
+                .map(|x| (0..width).rev().map(|y| x * width + y).collect())
+                .collect(),
+            Move::Down => (0..width)
+                .map(|y| (0..height).map(|x| x * width + y).collect())
+                .collect(),
+            Move::Up => (0..width)
+                .map(|y| (0..height).rev().map(|x| x * width + y).collect())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The afterstate of a move and the score it gains, or None if nothing moves
+    pub fn after_move(&self, mov: Move) -> Option<(SmallBoard, u64)> {
+        let mut after = self.clone();
+        let mut score = 0;
+        for line in self.lines(mov) {
+            let mut values: Vec<u64> = line.iter().map(|i| self.cells[*i]).collect();
+            score += slide_line(&mut values).iter().sum::<u64>();
+            for (i, value) in line.into_iter().zip(values) {
+                after.cells[i] = value;
+            }
+        }
+        if after == *self {
+            return None;
+        }
+        Some((after, score))
+    }
+
+    // Every board a spawn can lead to, with its probability
+    pub fn spawns(&self) -> Vec<(SmallBoard, f64)> {
+        let empty: Vec<usize> = (0..self.cells.len())
+            .filter(|i| self.cells[*i] == 0)
+            .collect();
+        let per_cell = 1.0 / empty.len() as f64;
+        let mut spawns = Vec::new();
+        for i in empty {
+            for (value, chance) in [(2, CHANCE_FOR_TWO), (4, 1.0 - CHANCE_FOR_TWO)] {
+                let mut board = self.clone();
+                board.cells[i] = value;
+                spawns.push((board, chance * per_cell));
+            }
+        }
+        spawns
+    }
+
+    fn max_tile(&self) -> u64 {
+        self.cells.iter().copied().max().unwrap_or(0)
+    }
+
+    fn pack(&self) -> u64 {
+        self.cells.iter().enumerate().fold(0, |key, (i, value)| {
+            let exponent = if *value == 0 {
+                0
+            } else {
+                value.trailing_zeros() as u64
+            };
+            key | exponent << (4 * i)
+        })
+    }
+
+    fn unpack(width: usize, height: usize, key: u64) -> Self {
+        let cells = (0..width * height)
+            .map(|i| match (key >> (4 * i)) & 0xF {
+                0 => 0,
+                exponent => 1 << exponent,
+            })
+            .collect();
+        Self::new(width, height, cells)
+    }
+}
+
+// What the tablebase maximizes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    // Expected score gained from the position to the end of the game
+    Score,
+    // Probability of reaching a tile at least this big
+    ReachTile(u64),
+}
+
+// The exact value under optimal play of every position reachable from the start of a
+// game, for boards small enough to enumerate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tablebase {
+    width: usize,
+    height: usize,
+    objective: Objective,
+    // Sorted by packed position
+    entries: Vec<(u64, f32)>,
+}
+
+impl Tablebase {
+    // The sum of the tiles grows with every spawn and stays the same with every move, so the
+    // positions are found sum by sum going forward, and valued sum by sum going backward.
+    pub fn generate(width: usize, height: usize, objective: Objective) -> Self {
+        let start = SmallBoard::empty(width, height);
+        let mut layers: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        let mut values: HashMap<u64, f64> = HashMap::new();
+        for (first, _) in start.spawns() {
+            for (board, _) in first.spawns() {
+                if values.insert(board.pack(), 0.0).is_none() {
+                    layers
+                        .entry(tile_sum(&board))
+                        .or_default()
+                        .push(board.pack());
+                }
+            }
+        }
+
+        let mut next_sum = 0;
+        while let Some((&sum, keys)) = layers.range(next_sum..).next() {
+            next_sum = sum + 1;
+            let mut found = Vec::new();
+            for key in keys {
+                let board = SmallBoard::unpack(width, height, *key);
+                if is_finished(&board, objective) {
+                    continue;
+                }
+                for mov in MOVES {
+                    if let Some((after, _)) = board.after_move(mov) {
+                        for (next, _) in after.spawns() {
+                            let key = next.pack();
+                            if values.insert(key, 0.0).is_none() {
+                                found.push((tile_sum(&next), key));
+                            }
+                        }
+                    }
+                }
+            }
+            for (sum, key) in found {
+                layers.entry(sum).or_default().push(key);
+            }
+        }
+
+        for keys in layers.values().rev() {
+            for key in keys {
+                let board = SmallBoard::unpack(width, height, *key);
+                let value = if is_finished(&board, objective) {
+                    1.0
+                } else {
+                    evaluate(&board, objective, |next| values.get(&next.pack()).copied())
+                        .into_iter()
+                        .filter_map(|(_, value)| value)
+                        .fold(0.0, f64::max)
+                };
+                values.insert(*key, value);
+            }
+        }
+
+        let mut entries: Vec<(u64, f32)> = values
+            .into_iter()
+            .map(|(key, value)| (key, value as f32))
+            .collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        Self {
+            width,
+            height,
+            objective,
+            entries,
+        }
+    }
+
+    pub fn get_objective(&self) -> Objective {
+        self.objective
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // None for positions that can't be reached or don't fit the table
+    pub fn value(&self, board: &SmallBoard) -> Option<f64> {
+        if (board.width, board.height) != (self.width, self.height) {
+            return None;
+        }
+        let key = board.pack();
+        let index = self
+            .entries
+            .binary_search_by_key(&key, |(key, _)| *key)
+            .ok()?;
+        Some(self.entries[index].1 as f64)
+    }
+
+    // The exact value of every valid move
+    pub fn evaluate_moves(&self, board: &SmallBoard) -> Option<Vec<(Move, f64)>> {
+        evaluate(board, self.objective, |next| self.value(next))
+            .into_iter()
+            .map(|(mov, value)| Some((mov, value?)))
+            .collect()
+    }
+
+    pub fn best_move(&self, board: &SmallBoard) -> Option<Move> {
+        self.evaluate_moves(board)?
+            .into_iter()
+            .fold(None, |best: Option<(Move, f64)>, (mov, value)| match best {
+                Some((_, best_value)) if best_value >= value => best,
+                _ => Some((mov, value)),
+            })
+            .map(|(mov, _)| mov)
+    }
+
+    // Binary format, numbers little endian: "TB48", the format version as u32, the width
+    // and height as one byte each, the objective as one byte (0 for score, 1 for a tile)
+    // followed by the target tile as u64 and the number of entries as u64. Every entry is
+    // the position with 4 bits per cell, in as few bytes as fit the board, and its value
+    // as f32.
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), TablebaseError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&[self.width as u8, self.height as u8])?;
+        let (kind, target) = match self.objective {
+            Objective::Score => (0, 0),
+            Objective::ReachTile(tile) => (1, tile),
+        };
+        writer.write_all(&[kind])?;
+        writer.write_all(&target.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        let key_bytes = key_bytes(self.width, self.height);
+        for (key, value) in self.entries.iter() {
+            writer.write_all(&key.to_le_bytes()[..key_bytes])?;
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Self, TablebaseError> {
+        let invalid = |reason: &str| TablebaseError::InvalidFormat(reason.to_string());
+        let mut header = [0; 11];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a tablebase file"));
+        }
+        if u32::from_le_bytes([header[4], header[5], header[6], header[7]]) != FORMAT_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        let (width, height) = (header[8] as usize, header[9] as usize);
+        if width * height == 0 || width * height > MAX_CELLS {
+            return Err(invalid("unsupported board size"));
+        }
+        let target = read_u64(reader)?;
+        let objective = match header[10] {
+            0 => Objective::Score,
+            1 => Objective::ReachTile(target),
+            _ => return Err(invalid("unknown objective")),
+        };
+        let count = read_u64(reader)?;
+        let key_bytes = key_bytes(width, height);
+        let mut entries = Vec::new();
+        let mut entry = vec![0; key_bytes + 4];
+        for _ in 0..count {
+            reader.read_exact(&mut entry)?;
+            let mut key = [0; 8];
+            key[..key_bytes].copy_from_slice(&entry[..key_bytes]);
+            let value = &entry[key_bytes..];
+            entries.push((
+                u64::from_le_bytes(key),
+                f32::from_le_bytes([value[0], value[1], value[2], value[3]]),
+            ));
+        }
+        if entries.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(invalid("entries aren't sorted"));
+        }
+        Ok(Self {
+            width,
+            height,
+            objective,
+            entries,
+        })
+    }
+
+    pub fn save_to_file(&self, path: &str) -> Result<(), TablebaseError> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.save(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> Result<Self, TablebaseError> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::load(&mut reader)
+    }
+}
+
+fn tile_sum(board: &SmallBoard) -> u64 {
+    board.cells.iter().sum()
+}
+
+fn is_finished(board: &SmallBoard, objective: Objective) -> bool {
+    match objective {
+        Objective::Score => false,
+        Objective::ReachTile(tile) => board.max_tile() >= tile,
+    }
+}
+
+// The value of every valid move given the values of the positions after the spawns.
+// A move whose spawns can't all be valued gets None.
+fn evaluate<F>(board: &SmallBoard, objective: Objective, value: F) -> Vec<(Move, Option<f64>)>
+where
+    F: Fn(&SmallBoard) -> Option<f64>,
+{
+    MOVES
+        .iter()
+        .filter_map(|mov| {
+            let (after, score) = board.after_move(*mov)?;
+            let expected: Option<f64> = after
+                .spawns()
+                .iter()
+                .map(|(next, probability)| Some(probability * value(next)?))
+                .sum();
+            let reward = match objective {
+                Objective::Score => score as f64,
+                Objective::ReachTile(_) => 0.0,
+            };
+            Some((*mov, expected.map(|expected| reward + expected)))
+        })
+        .collect()
+}
+
+fn key_bytes(width: usize, height: usize) -> usize {
+    (4 * width * height).div_ceil(8)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, TablebaseError> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Objective, SmallBoard, Tablebase};
+    use crate::grid::Move;
+
+    #[test]
+    fn moves_work_on_small_boards() {
+        let board = SmallBoard::new(3, 2, vec![2, 2, 4, 0, 4, 4]);
+        let (left, score) = board.after_move(Move::Left).unwrap();
+        assert_eq!(left.get_cells(), &[4, 4, 0, 8, 0, 0]);
+        assert_eq!(score, 12);
+
+        let (down, score) = board.after_move(Move::Down).unwrap();
+        assert_eq!(down.get_cells(), &[0, 2, 0, 2, 4, 8]);
+        assert_eq!(score, 8);
+        assert!(board.after_move(Move::UpLeft).is_none());
+    }
+
+    #[test]
+    fn one_by_two_board_has_known_values() {
+        // Merging the 2s scores 4, and the spawn is a 4 that merges again one time in ten
+        let score = Tablebase::generate(2, 1, Objective::Score);
+        let board = SmallBoard::new(2, 1, vec![2, 2]);
+        assert!((score.value(&board).unwrap() - 4.8).abs() < 1e-6);
+
+        let eight = Tablebase::generate(2, 1, Objective::ReachTile(8));
+        assert!((eight.value(&board).unwrap() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn values_are_the_best_move_values() {
+        let table = Tablebase::generate(2, 2, Objective::Score);
+        assert!(!table.is_empty());
+        let board = SmallBoard::new(2, 2, vec![2, 0, 4, 0]);
+        let evaluations = table.evaluate_moves(&board).unwrap();
+        let best = evaluations
+            .iter()
+            .map(|(_, value)| *value)
+            .fold(0.0, f64::max);
+        assert!((table.value(&board).unwrap() - best).abs() < 1e-3);
+        assert!(table.best_move(&board).is_some());
+
+        let unreachable = SmallBoard::new(2, 2, vec![1024, 0, 0, 0]);
+        assert_eq!(table.value(&unreachable), None);
+    }
+
+    #[test]
+    fn probabilities_stay_between_zero_and_one() {
+        let table = Tablebase::generate(3, 2, Objective::ReachTile(64));
+        let board = SmallBoard::new(3, 2, vec![2, 0, 0, 0, 0, 2]);
+        let value = table.value(&board).unwrap();
+        assert!(value > 0.0 && value <= 1.0);
+    }
+
+    #[test]
+    fn tables_survive_a_round_trip() {
+        let table = Tablebase::generate(2, 2, Objective::ReachTile(32));
+        let mut bytes = Vec::new();
+        table.save(&mut bytes).unwrap();
+        // 27 bytes of header and 2 + 4 bytes per entry
+        assert_eq!(bytes.len(), 27 + 6 * table.len());
+        assert_eq!(Tablebase::load(&mut bytes.as_slice()).unwrap(), table);
+        assert!(Tablebase::load(&mut &bytes[1..]).is_err());
+    }
+}
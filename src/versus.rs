@@ -0,0 +1,187 @@
+use crate::grid::{GameStatus, Grid, Move};
+
+// Merges creating a tile of at least this value send garbage to the opponent
+const GARBAGE_THRESHOLD: u64 = 64;
+const GARBAGE_VALUE: u64 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    fn index(&self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+
+    fn opponent(&self) -> Player {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
+}
+
+// Two boards played side by side. Big merges on one board queue junk tiles for the
+// other board, which are dropped onto it right after that player's next move.
+pub struct Versus {
+    grids: [Grid; 2],
+    pending_garbage: [usize; 2],
+    winner: Option<Player>,
+}
+
+impl Versus {
+    fn new(grids: [Grid; 2]) -> Self {
+        Self {
+            grids,
+            pending_garbage: [0, 0],
+            winner: None,
+        }
+    }
+
+    pub fn new_random() -> Self {
+        Self::new([Grid::new_random(), Grid::new_random()])
+    }
+
+    pub fn get_grid(&self, player: Player) -> &Grid {
+        &self.grids[player.index()]
+    }
+
+    pub fn get_pending_garbage(&self, player: Player) -> usize {
+        self.pending_garbage[player.index()]
+    }
+
+    pub fn get_winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    pub fn attempt(&mut self, player: Player, mov: Move) -> GameStatus {
+        if self.winner.is_some() {
+            return GameStatus::Lost;
+        }
+        let me = player.index();
+        let opponent = player.opponent().index();
+
+        let mut status = self.grids[me].attempt(mov);
+        if status == GameStatus::InvalidMove {
+            return status;
+        }
+
+        // Garbage we send first cancels out garbage that is waiting for us, like in Tetris
+        let sent = Self::garbage_for_merges(self.grids[me].get_last_merges());
+        let cancelled = sent.min(self.pending_garbage[me]);
+        self.pending_garbage[me] -= cancelled;
+        self.pending_garbage[opponent] += sent - cancelled;
+
+        let incoming = std::mem::take(&mut self.pending_garbage[me]);
+        Self::drop_garbage(&mut self.grids[me], incoming);
+        if self.grids[me].has_player_lost() {
+            status = GameStatus::Lost;
+        }
+
+        if status == GameStatus::Lost {
+            self.winner = Some(player.opponent());
+        }
+        status
+    }
+
+    // A merge into 64 sends one junk tile, 128 sends two, 256 sends three and so on.
+    fn garbage_for_merges(merges: &[u64]) -> usize {
+        merges
+            .iter()
+            .filter(|value| **value >= GARBAGE_THRESHOLD)
+            .map(|value| (value.trailing_zeros() - GARBAGE_THRESHOLD.trailing_zeros() + 1) as usize)
+            .sum()
+    }
+
+    // Junk rises from the bottom: it fills empty cells starting with the bottom row,
+    // left to right. Junk that doesn't fit on the board is discarded.
+    fn drop_garbage(grid: &mut Grid, count: usize) {
+        let mut remaining = count;
+        for x in (0..4).rev() {
+            for y in 0..4 {
+                if remaining == 0 {
+                    return;
+                }
+                if grid.insert_cell(x, y, GARBAGE_VALUE) {
+                    remaining -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Versus {
+    fn default() -> Self {
+        Self::new_random()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Player, Versus};
+    use crate::grid::{GameStatus, Grid, Move};
+
+    #[test]
+    fn small_merges_send_no_garbage() {
+        assert_eq!(Versus::garbage_for_merges(&[4, 8, 32]), 0);
+    }
+
+    #[test]
+    fn big_merges_send_garbage() {
+        assert_eq!(Versus::garbage_for_merges(&[64]), 1);
+        assert_eq!(Versus::garbage_for_merges(&[128]), 2);
+        assert_eq!(Versus::garbage_for_merges(&[64, 256, 4]), 4);
+    }
+
+    #[test]
+    fn garbage_fills_from_the_bottom_row() {
+        let row1 = [0, 0, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [8, 0, 16, 0];
+        let mut grid = Grid::new([row1, row2, row3, row4]);
+        Versus::drop_garbage(&mut grid, 3);
+
+        let row3 = [2, 0, 0, 0];
+        let row4 = [8, 2, 16, 2];
+        assert_eq!(grid.cells, [row1, row2, row3, row4]);
+    }
+
+    #[test]
+    fn garbage_is_delivered_after_opponents_next_move() {
+        let row1 = [32, 32, 0, 0];
+        let row2 = [0, 0, 0, 0];
+        let row3 = [0, 0, 0, 0];
+        let row4 = [0, 0, 0, 0];
+        let one = Grid::new([row1, row2, row3, row4]);
+        let two = Grid::new([row1, row2, row3, row4]);
+        let mut versus = Versus::new([one, two]);
+
+        assert_eq!(versus.attempt(Player::One, Move::Right), GameStatus::Ok);
+        assert_eq!(versus.get_pending_garbage(Player::Two), 1);
+
+        // Player two also merges into 64, cancelling the garbage instead of receiving it
+        assert_eq!(versus.attempt(Player::Two, Move::Right), GameStatus::Ok);
+        assert_eq!(versus.get_pending_garbage(Player::One), 0);
+        assert_eq!(versus.get_pending_garbage(Player::Two), 0);
+    }
+
+    #[test]
+    fn losing_board_makes_the_opponent_win() {
+        let row1 = [2, 4, 2, 4];
+        let row2 = [4, 2, 4, 2];
+        let row3 = [8, 64, 8, 64];
+        let row4 = [16, 32, 8, 8];
+        let one = Grid::new([row1, row2, row3, row4]);
+        let two = Grid::new([[2, 0, 0, 0], [0; 4], [0; 4], [0; 4]]);
+        let mut versus = Versus::new([one, two]);
+
+        assert_eq!(versus.attempt(Player::One, Move::Right), GameStatus::Lost);
+        assert_eq!(versus.get_winner(), Some(Player::Two));
+    }
+}